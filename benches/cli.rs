@@ -0,0 +1,22 @@
+//! End-to-end latency of an executable of the fixture workspace, run with 'cargo bench'
+
+use bintest::BinTest;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn producer(c: &mut Criterion) {
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .build_workspace(true)
+        .for_benches()
+        .build();
+
+    c.bench_function("producer", |b| {
+        b.iter(|| {
+            let output = bins.command("producer").arg("bench").output().unwrap();
+            assert!(output.status.success());
+        })
+    });
+}
+
+criterion_group!(benches, producer);
+criterion_main!(benches);
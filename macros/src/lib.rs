@@ -0,0 +1,114 @@
+//! The `#[bintest::test]` attribute, enable the 'macros' feature of 'bintest' to use it.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::parse::Parse;
+use syn::{parse_macro_input, Ident, ItemFn, LitStr, Token};
+
+/// Turns `fn my_test(bins: &BinTest)` into a `#[test]`, the `BinTest` is build once per
+/// configuration with `BinTestBuilder::build_cached()` and shared by all tests.
+///
+/// Options: `workspace`, `all_features`, `no_default_features`, `release`, `examples`,
+/// `quiet`, `features("a", "b")`, `package("name")`, `bin("name")` and `profile("name")`.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut builder = TokenStream2::new();
+    let parser = syn::meta::parser(|meta| option(&meta, &mut builder));
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    expand(function, builder)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Parses one option of the attribute into calls on the builder
+fn option(meta: &ParseNestedMeta, builder: &mut TokenStream2) -> syn::Result<()> {
+    let name = match meta.path.get_ident() {
+        Some(name) => name.to_string(),
+        None => return Err(meta.error("unknown bintest option")),
+    };
+    let flag = match name.as_str() {
+        "workspace" => Some(quote!(build_workspace)),
+        "all_features" => Some(quote!(all_features)),
+        "no_default_features" => Some(quote!(no_default_features)),
+        "release" => Some(quote!(release)),
+        "examples" => Some(quote!(build_examples)),
+        "quiet" => Some(quote!(quiet)),
+        _ => None,
+    };
+    if let Some(method) = flag {
+        if !meta.input.is_empty() && !meta.input.peek(Token![,]) {
+            return Err(meta.error(format!("<<{}>> takes no arguments", name)));
+        }
+        builder.extend(quote!(.#method(true)));
+        return Ok(());
+    }
+
+    let method = match name.as_str() {
+        "features" => quote!(features),
+        "package" => quote!(package),
+        "bin" => quote!(build_executable),
+        "profile" => quote!(profile),
+        _ => return Err(meta.error(format!("unknown bintest option <<{}>>", name))),
+    };
+    if !meta.input.peek(syn::token::Paren) {
+        return Err(meta.error(format!("<<{}>> expects strings: {}(\"..\")", name, name)));
+    }
+    let content;
+    syn::parenthesized!(content in meta.input);
+    let values = content.parse_terminated(<LitStr as Parse>::parse, Token![,])?;
+    if values.is_empty() {
+        return Err(meta.error(format!("<<{}>> needs a string", name)));
+    }
+    if name == "features" {
+        let values = values.iter();
+        builder.extend(quote!(.features([#(#values),*])));
+        return Ok(());
+    }
+    if values.len() > 1 && name == "profile" {
+        return Err(meta.error("<<profile>> takes a single string"));
+    }
+    for value in values {
+        builder.extend(quote!(.#method(#value)));
+    }
+    Ok(())
+}
+
+/// Wraps the function into a `#[test]` which passes the shared `BinTest` in
+fn expand(function: ItemFn, builder: TokenStream2) -> syn::Result<TokenStream2> {
+    if function.sig.inputs.len() != 1 {
+        return Err(syn::Error::new_spanned(
+            &function.sig,
+            "a bintest test takes exactly one argument, the '&BinTest'",
+        ));
+    }
+    if let Some(asyncness) = &function.sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "a bintest test can not be async",
+        ));
+    }
+
+    let attrs = &function.attrs;
+    let vis = &function.vis;
+    let name = &function.sig.ident;
+    let output = &function.sig.output;
+    let inner = Ident::new("__bintest_test", name.span());
+    let mut inner_function = function.clone();
+    inner_function.attrs.clear();
+    inner_function.vis = syn::Visibility::Inherited;
+    inner_function.sig.ident = inner.clone();
+
+    Ok(quote! {
+        #[test]
+        #(#attrs)*
+        #vis fn #name() #output {
+            #inner_function
+            let bintest = ::bintest::BinTest::with() #builder .build_cached();
+            #inner(&bintest)
+        }
+    })
+}
@@ -0,0 +1,211 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::time::UNIX_EPOCH;
+
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+
+use crate::ExecutableInfo;
+
+// bump whenever the layout changes, older files are then ignored
+const FORMAT: &str = "bintest registry 5";
+
+// environment variables which change what cargo builds
+const BUILD_ENV: &[&str] = &[
+    "CARGO",
+    "CARGO_BUILD_TARGET",
+    "CARGO_ENCODED_RUSTFLAGS",
+    "CARGO_TARGET_DIR",
+    "RUSTC",
+    "RUSTFLAGS",
+    "RUSTUP_TOOLCHAIN",
+];
+
+/// The registry of a previous build, as written to disk
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    pub(crate) executables: BTreeMap<String, Utf8PathBuf>,
+    pub(crate) libraries: BTreeMap<String, Utf8PathBuf>,
+    pub(crate) debug_infos: BTreeMap<String, Utf8PathBuf>,
+    pub(crate) infos: BTreeMap<String, ExecutableInfo>,
+    pub(crate) ambiguous: BTreeMap<String, Vec<String>>,
+    pub(crate) profile: String,
+    pub(crate) target: Option<String>,
+}
+
+/// Location and fingerprint of the cached registry for one builder configuration
+pub(crate) struct RegistryCache {
+    file: Utf8PathBuf,
+    fingerprint: u64,
+}
+
+impl RegistryCache {
+    /// The fingerprint covers the configuration, the build environment, the compiler version
+    /// as given by 'rustc -vV' and the size and modification time of every file in the
+    /// workspace outside of the target directory
+    pub(crate) fn new(
+        workspace_root: &Utf8Path,
+        target_dir: &Utf8Path,
+        config: &str,
+        rustc_version: &str,
+    ) -> Self {
+        let mut config_hasher = DefaultHasher::new();
+        config.hash(&mut config_hasher);
+        let file = target_dir.join(format!("bintest-registry-{:016x}", config_hasher.finish()));
+
+        let mut hasher = DefaultHasher::new();
+        FORMAT.hash(&mut hasher);
+        config.hash(&mut hasher);
+        rustc_version.hash(&mut hasher);
+        for key in BUILD_ENV {
+            std::env::var_os(key).hash(&mut hasher);
+        }
+        hash_sources(workspace_root, target_dir, &mut hasher);
+
+        RegistryCache {
+            file,
+            fingerprint: hasher.finish(),
+        }
+    }
+
+    /// Returns the cached registry when it was written by the same format version with the
+    /// same fingerprint
+    pub(crate) fn load(&self) -> Option<Registry> {
+        let contents = fs::read_to_string(&self.file).ok()?;
+        let mut lines = contents.lines();
+        if lines.next()? != FORMAT {
+            return None;
+        }
+        if lines.next()? != format!("fingerprint {:016x}", self.fingerprint) {
+            return None;
+        }
+
+        let mut registry = Registry::default();
+        for line in lines {
+            let (kind, rest) = line.split_once(' ')?;
+            match kind {
+                "profile" => registry.profile = String::from(rest),
+                "target" => registry.target = Some(String::from(rest)),
+                "exe" => {
+                    let (name, path) = rest.split_once('\t')?;
+                    registry
+                        .executables
+                        .insert(String::from(name), Utf8PathBuf::from(path));
+                }
+                "lib" => {
+                    let (name, path) = rest.split_once('\t')?;
+                    registry
+                        .libraries
+                        .insert(String::from(name), Utf8PathBuf::from(path));
+                }
+                "dbg" => {
+                    let (name, path) = rest.split_once('\t')?;
+                    registry
+                        .debug_infos
+                        .insert(String::from(name), Utf8PathBuf::from(path));
+                }
+                "info" => {
+                    let mut fields = rest.split('\t').map(String::from);
+                    let name = fields.next()?;
+                    let info = ExecutableInfo {
+                        package_id: fields.next()?,
+                        package: fields.next()?,
+                        kind: fields.next()?,
+                        profile: fields.next()?,
+                        features: fields.collect(),
+                        #[cfg(feature = "artifact")]
+                        artifact: None,
+                    };
+                    registry.infos.insert(name, info);
+                }
+                "ambiguous" => {
+                    let mut fields = rest.split('\t').map(String::from);
+                    let name = fields.next()?;
+                    registry.ambiguous.insert(name, fields.collect());
+                }
+                _ => return None,
+            }
+        }
+        Some(registry)
+    }
+
+    /// Writes the registry, names or paths which can not be represented are an error
+    pub(crate) fn store(&self, registry: &Registry) -> io::Result<()> {
+        let invalid = |value: &str| value.contains(['\t', '\n', '\r']);
+        let mut contents = format!("{}\nfingerprint {:016x}\n", FORMAT, self.fingerprint);
+        contents.push_str(&format!("profile {}\n", registry.profile));
+        if let Some(target) = &registry.target {
+            contents.push_str(&format!("target {}\n", target));
+        }
+        let entries = registry
+            .executables
+            .iter()
+            .map(|entry| ("exe", entry))
+            .chain(registry.libraries.iter().map(|entry| ("lib", entry)))
+            .chain(registry.debug_infos.iter().map(|entry| ("dbg", entry)));
+        for (kind, (name, path)) in entries {
+            if invalid(name) || invalid(path.as_str()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("can not store <<{}>>", name),
+                ));
+            }
+            contents.push_str(&format!("{} {}\t{}\n", kind, name, path));
+        }
+        for (name, info) in &registry.infos {
+            contents.push_str(&format!(
+                "info {}\t{}\t{}\t{}\t{}",
+                name, info.package_id, info.package, info.kind, info.profile
+            ));
+            for feature in &info.features {
+                contents.push_str(&format!("\t{}", feature));
+            }
+            contents.push('\n');
+        }
+        for (name, qualified) in &registry.ambiguous {
+            contents.push_str(&format!("ambiguous {}\t{}\n", name, qualified.join("\t")));
+        }
+
+        // written aside and renamed, concurrent readers never see a partial file
+        let partial = self.file.with_extension("partial");
+        fs::create_dir_all(self.file.parent().unwrap_or(&self.file))?;
+        fs::write(&partial, contents)?;
+        fs::rename(&partial, &self.file)
+    }
+}
+
+/// Hashes path, size and modification time of all files below `dir`, skipping the target
+/// directory and hidden directories like '.git'
+fn hash_sources(dir: &Utf8Path, target_dir: &Utf8Path, hasher: &mut DefaultHasher) {
+    let mut entries: Vec<_> = match dir.read_dir_utf8() {
+        Ok(entries) => entries.filter_map(Result::ok).collect(),
+        Err(_) => return,
+    };
+    entries.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+
+    for entry in entries {
+        let path = entry.path();
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.is_dir() {
+            if !entry.file_name().starts_with('.')
+                && entry.file_name() != "target"
+                && path != target_dir
+            {
+                hash_sources(path, target_dir, hasher);
+            }
+            continue;
+        }
+        path.hash(hasher);
+        metadata.len().hash(hasher);
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .hash(hasher);
+    }
+}
@@ -0,0 +1,588 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use cargo_metadata::camino::Utf8PathBuf;
+
+use crate::BuildFailure;
+
+/// Everything that can go wrong while building and registering the executables
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The builder configuration contains options that can not be combined
+    InvalidConfig(ConfigError),
+    /// The manifest given by `manifest_path()` does not exist
+    ManifestNotFound(Utf8PathBuf),
+    /// A package given by `package()` is not a member of the workspace
+    UnknownPackage(String),
+    /// Running 'cargo metadata' failed
+    Metadata(cargo_metadata::Error),
+    /// cargo could not be started
+    Spawn(io::Error),
+    /// The directory of `ephemeral_target_dir()` could not be created
+    TempDir(io::Error),
+    /// The lock file of `cross_process_lock()` could not be created or locked
+    Lock(io::Error),
+    /// Another process held the lock file of `cross_process_lock()` for too long
+    LockTimeout {
+        /// Path of the lock file
+        lockfile: Utf8PathBuf,
+        /// Process id of the current holder, when known
+        holder: Option<u32>,
+    },
+    /// The JSON messages from cargo could not be read or parsed
+    Json(io::Error),
+    /// The file given to `BinTest::read_manifest()` could not be read or parsed
+    ReadManifest(io::Error),
+    /// cargo exited with a failure
+    BuildFailed(BuildError),
+    /// The build took longer than given by `timeout()` and was killed
+    Timeout {
+        /// The configured timeout
+        timeout: Duration,
+        /// Name of the last target cargo reported as build
+        last_artifact: Option<String>,
+    },
+    /// An offline build needs dependencies that are not available locally
+    Offline(ExitStatus),
+    /// The toolchain given by `toolchain()` is not installed
+    ToolchainNotInstalled(String),
+    /// The program of `BinTestBuilder::runner()`, `run_under_wine()` or
+    /// `BinTest::try_command_under()` is not installed, tests can be skipped on this error
+    RunnerNotFound(OsString),
+    /// The executable given to `BinTest::try_command_under()` is not registered
+    Lookup(LookupError),
+    /// The configuration needs a nightly toolchain, see `BinTestBuilder::toolchain()`
+    NightlyRequired {
+        /// What needs nightly
+        feature: String,
+        /// The release of the active rustc
+        found: String,
+    },
+    /// Running under Miri, which can not run cargo, and no 'CARGO_BIN_EXE_<name>' variables
+    /// are set
+    UnsupportedUnderMiri,
+    /// A package requires a newer rustc than the active one
+    RustVersion {
+        /// Name of the package
+        package: String,
+        /// The 'rust-version' of the package
+        required: String,
+        /// The version of the active rustc
+        found: String,
+    },
+    /// 'Cargo.lock' was modified although locked or frozen mode was requested
+    LockfileModified {
+        /// Path of the lockfile
+        lockfile: Utf8PathBuf,
+        /// Either "locked" or "frozen"
+        mode: &'static str,
+    },
+    /// An executable given by `build_executable()` requires features that are not enabled
+    MissingFeatures {
+        /// Name of the executable
+        executable: String,
+        /// The features that need to be enabled
+        features: Vec<String>,
+    },
+    /// A target given by `build_executable()` or `build_example()` does not exist
+    UnknownTarget {
+        /// Either "bin" or "example"
+        kind: &'static str,
+        /// The requested name
+        name: String,
+        /// The most similar existing target
+        suggestion: Option<String>,
+        /// All targets of this kind in the workspace
+        available: Vec<String>,
+    },
+    /// Executables of several packages have the same name, see
+    /// `BinTestBuilder::qualify_duplicates()`
+    DuplicateExecutable {
+        /// Name of the executable
+        name: String,
+        /// The packages which contain an executable with this name
+        packages: Vec<String>,
+    },
+    /// The compiler emitted warnings for workspace members in `fail_on_warnings()` mode,
+    /// contains the rendered warnings
+    Warnings(Vec<String>),
+    /// Targets had to be compiled in `expect_fresh()` mode, contains their names
+    NotFresh(Vec<String>),
+    /// Executables given by `build_executable()` were not build
+    MissingExecutables(Vec<String>),
+    /// Registered executables are missing or not executable after the build
+    InvalidExecutables(VerifyReport),
+    /// The build did not produce any executables, see `BinTestBuilder::allow_empty()`
+    NoExecutables {
+        /// The targets of workspace members that were build
+        built: Vec<String>,
+        /// The filters which limited the build, like '--bin name'
+        filters: Vec<String>,
+        /// Hints where executables could be found
+        suggestions: Vec<String>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidConfig(err) => err.fmt(f),
+            Error::ManifestNotFound(path) => write!(f, "manifest <<{}>> does not exist", path),
+            Error::UnknownPackage(spec) => {
+                write!(f, "package <<{}>> is not a workspace member", spec)
+            }
+            Error::Metadata(err) => write!(f, "'cargo metadata' failed: {}", err),
+            Error::Spawn(err) => write!(
+                f,
+                "could not run 'cargo build': {}, is cargo installed and in PATH?",
+                err
+            ),
+            Error::TempDir(err) => {
+                write!(f, "could not create a temporary target directory: {}", err)
+            }
+            Error::Lock(err) => write!(f, "could not take the build lock: {}", err),
+            Error::LockTimeout { lockfile, holder } => {
+                write!(f, "timed out waiting for <<{}>>", lockfile)?;
+                match holder {
+                    Some(pid) => write!(f, ", held by process {}", pid),
+                    None => Ok(()),
+                }
+            }
+            Error::Json(err) => write!(f, "could not parse the output of 'cargo build': {}", err),
+            Error::ReadManifest(err) => write!(f, "could not read the manifest: {}", err),
+            Error::BuildFailed(err) => err.fmt(f),
+            Error::Timeout {
+                timeout,
+                last_artifact,
+            } => {
+                write!(f, "'cargo build' did not finish within {:?}", timeout)?;
+                match last_artifact {
+                    Some(artifact) => write!(f, ", the last target build was <<{}>>", artifact),
+                    None => write!(f, ", no target was build"),
+                }
+            }
+            Error::Offline(status) => write!(
+                f,
+                "'cargo build --offline' failed because some dependencies are not available \
+                 locally, vendor them with 'cargo vendor' or fetch them with 'cargo fetch' \
+                 before running the tests ({})",
+                status
+            ),
+            Error::ToolchainNotInstalled(toolchain) => write!(
+                f,
+                "toolchain <<{0}>> is not installed, install it with \
+                 'rustup toolchain install {0}'",
+                toolchain
+            ),
+            Error::RunnerNotFound(program) => write!(
+                f,
+                "runner <<{}>> was not found in PATH",
+                program.to_string_lossy()
+            ),
+            Error::Lookup(err) => err.fmt(f),
+            Error::NightlyRequired { feature, found } => write!(
+                f,
+                "{} requires a nightly toolchain, the active rustc is {}, \
+                 select one with toolchain(\"nightly\")",
+                feature, found
+            ),
+            Error::UnsupportedUnderMiri => write!(
+                f,
+                "process spawning is not supported under Miri; skip or gate these tests \
+                 with #[cfg_attr(miri, ignore)]"
+            ),
+            Error::RustVersion {
+                package,
+                required,
+                found,
+            } => write!(
+                f,
+                "package {} requires rustc {}, you have {}",
+                package, required, found
+            ),
+            Error::LockfileModified { lockfile, mode } => write!(
+                f,
+                "<<{}>> was modified by the build although {} mode was requested",
+                lockfile, mode
+            ),
+            Error::MissingFeatures {
+                executable,
+                features,
+            } => write!(
+                f,
+                "executable <<{}>> requires the features {:?} which are not enabled",
+                executable, features
+            ),
+            Error::UnknownTarget {
+                kind,
+                name,
+                suggestion,
+                available,
+            } => {
+                write!(
+                    f,
+                    "no {} target named <<{}>>, available are: {}",
+                    kind,
+                    name,
+                    available.join(", ")
+                )?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean <<{}>>?", suggestion)?;
+                }
+                Ok(())
+            }
+            Error::DuplicateExecutable { name, packages } => write!(
+                f,
+                "executable <<{}>> exists in the packages {}, use \
+                 'qualify_duplicates(true)' to register them as 'package/{}'",
+                name,
+                packages.join(", "),
+                name
+            ),
+            Error::Warnings(warnings) => {
+                write!(
+                    f,
+                    "the build emitted {} warnings in workspace members:",
+                    warnings.len()
+                )?;
+                for warning in warnings {
+                    write!(f, "\n{}", crate::strip_ansi(warning))?;
+                }
+                Ok(())
+            }
+            Error::NotFresh(targets) => write!(
+                f,
+                "expected a fresh build but these targets were compiled: {}",
+                targets.join(", ")
+            ),
+            Error::MissingExecutables(names) => {
+                write!(f, "requested executables not build: {:?}", names)
+            }
+            Error::InvalidExecutables(report) => report.fmt(f),
+            Error::NoExecutables {
+                built,
+                filters,
+                suggestions,
+            } => {
+                write!(f, "the build did not produce any executables")?;
+                if built.is_empty() {
+                    write!(f, "\n  nothing was build")?;
+                } else {
+                    write!(f, "\n  build were: {}", built.join(", "))?;
+                }
+                if !filters.is_empty() {
+                    write!(f, "\n  active filters: {}", filters.join(" "))?;
+                }
+                for suggestion in suggestions {
+                    write!(f, "\n  {}", suggestion)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidConfig(err) => Some(err),
+            Error::Metadata(err) => Some(err),
+            Error::Spawn(err)
+            | Error::Json(err)
+            | Error::ReadManifest(err)
+            | Error::Lock(err)
+            | Error::TempDir(err) => Some(err),
+            Error::BuildFailed(err) => Some(err),
+            Error::Lookup(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The contradicting options found by `BinTestBuilder::validate()`
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    /// One message per conflict
+    pub conflicts: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.conflicts.as_slice() {
+            [conflict] => write!(f, "invalid configuration: {}", conflict),
+            conflicts => {
+                write!(f, "invalid configuration:")?;
+                for conflict in conflicts {
+                    write!(f, "\n  - {}", conflict)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Returned by `BinTest::merge()` for names which are registered in both with different paths
+#[derive(Debug, Clone)]
+pub struct MergeError {
+    /// The names registered in both, for `MergePolicy::Qualify` the ones which could not be
+    /// qualified by their packages
+    pub names: Vec<String>,
+    /// The libraries of `BinTestBuilder::include_libraries()` registered in both, they can
+    /// not be qualified
+    pub libraries: Vec<String>,
+    /// The packages of `BinTestBuilder::collect_build_scripts()` whose build scripts have a
+    /// different output in both, they can not be qualified
+    pub build_scripts: Vec<String>,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.names.is_empty() {
+            parts.push(format!(
+                "executables registered in both: <<{}>>",
+                self.names.join(">>, <<")
+            ));
+        }
+        if !self.libraries.is_empty() {
+            parts.push(format!(
+                "libraries registered in both: <<{}>>",
+                self.libraries.join(">>, <<")
+            ));
+        }
+        if !self.build_scripts.is_empty() {
+            parts.push(format!(
+                "build scripts of packages in both: <<{}>>",
+                self.build_scripts.join(">>, <<")
+            ));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Returned by `BinTest::run_timeout()`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TimeoutError {
+    /// The executable could not be started or waited for
+    Spawn(io::Error),
+    /// The executable did not finish in time and was terminated
+    Expired {
+        /// The timeout that expired
+        timeout: Duration,
+        /// Time from starting the executable until it exited after being terminated
+        elapsed: Duration,
+        /// Exit status and the output produced until it was terminated
+        output: std::process::Output,
+    },
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutError::Spawn(err) => write!(f, "failed to run the executable: {}", err),
+            TimeoutError::Expired {
+                timeout, elapsed, ..
+            } => write!(
+                f,
+                "did not finish within {:?}, terminated after {:.1}s",
+                timeout,
+                elapsed.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TimeoutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TimeoutError::Spawn(err) => Some(err),
+            TimeoutError::Expired { .. } => None,
+        }
+    }
+}
+
+/// A glob pattern that can not be used for matching executable names
+#[derive(Debug, Clone)]
+pub struct PatternError {
+    /// The pattern as given
+    pub pattern: String,
+    /// What is wrong with it
+    pub reason: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pattern <<{}>>: {}", self.pattern, self.reason)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Returned when looking up an executable that is not registered
+#[derive(Debug, Clone)]
+pub struct LookupError {
+    /// The requested name
+    pub name: String,
+    /// Names of all registered executables
+    pub available: Vec<String>,
+    /// The qualified names when the name is shared by executables of several packages
+    pub ambiguous: Vec<String>,
+    /// The compiler errors when the executable failed to build in `keep_going()` mode
+    pub build_failure: Option<Box<BuildFailure>>,
+    /// The executable was removed after the build, see `BinTestBuilder::verify_on_command()`
+    pub removed: bool,
+    /// The executable is not registered because of `BinTestBuilder::exclude_executable()`
+    pub excluded: bool,
+    /// A registered name that differs only in case or a '.exe' or '.bat' suffix
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.removed {
+            return write!(
+                f,
+                "artifact of executable <<{}>> was removed after build, rerun the build",
+                self.name
+            );
+        }
+        if self.excluded {
+            return write!(
+                f,
+                "executable <<{}>> was excluded by exclude_executable() in the configuration",
+                self.name
+            );
+        }
+        if !self.ambiguous.is_empty() {
+            return write!(
+                f,
+                "executable <<{}>> is ambiguous, use one of: {}",
+                self.name,
+                self.ambiguous.join(", ")
+            );
+        }
+        match &self.build_failure {
+            Some(failure) => write!(
+                f,
+                "executable <<{}>> failed to build:\n{}",
+                self.name,
+                failure.diagnostics_plain()
+            ),
+            None => {
+                write!(f, "no such executable <<{}>>, ", self.name)?;
+                if let Some(suggestion) = &self.suggestion {
+                    write!(f, "did you mean <<{}>>? ", suggestion)?;
+                }
+                write!(f, "available are: {}", self.available.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+/// What is wrong with a registered executable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ArtifactProblem {
+    /// The path does not exist (anymore)
+    Missing,
+    /// The path exists but is not a file
+    NotAFile,
+    /// The file has no executable permission
+    NotExecutable,
+}
+
+impl fmt::Display for ArtifactProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArtifactProblem::Missing => "does not exist",
+            ArtifactProblem::NotAFile => "is not a file",
+            ArtifactProblem::NotExecutable => "is not executable",
+        })
+    }
+}
+
+/// A registered executable that failed the verification
+#[derive(Debug, Clone)]
+pub struct InvalidExecutable {
+    /// The registered name
+    pub name: String,
+    /// The registered path
+    pub path: Utf8PathBuf,
+    /// What is wrong with it
+    pub problem: ArtifactProblem,
+}
+
+/// Result of `BinTest::verify()`, lists every registered executable which can not be run
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// The executables that are missing or invalid
+    pub invalid: Vec<InvalidExecutable>,
+}
+
+impl VerifyReport {
+    /// Returns true when all registered executables are fine
+    pub fn is_ok(&self) -> bool {
+        self.invalid.is_empty()
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "all registered executables are fine");
+        }
+        write!(f, "registered executables can not be run:")?;
+        for invalid in &self.invalid {
+            write!(
+                f,
+                "\n  <<{}>> at <<{}>> {}",
+                invalid.name, invalid.path, invalid.problem
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VerifyReport {}
+
+/// A failed build together with the compiler errors collected from cargo's messages
+#[derive(Debug, Clone)]
+pub struct BuildError {
+    /// Exit status of cargo
+    pub status: ExitStatus,
+    /// The compiler errors grouped by target
+    pub failures: Vec<BuildFailure>,
+    /// The stderr output of cargo when it was captured, empty otherwise
+    pub output: String,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'cargo build' failed: {}", self.status)?;
+        for failure in &self.failures {
+            write!(
+                f,
+                "\n\nerrors in target <<{}>>:\n{}",
+                failure.target,
+                failure.diagnostics_plain()
+            )?;
+        }
+        if !self.output.is_empty() {
+            write!(f, "\n\ncargo output:\n{}", crate::strip_ansi(&self.output))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BuildError {}
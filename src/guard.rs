@@ -0,0 +1,58 @@
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::process::{Child, Command};
+
+use crate::kill_process_tree;
+
+/// A spawned executable which is killed together with its children when dropped, created by
+/// `BinTest::spawn_guarded()`. Dereferences to the 'std::process::Child'. Children are left
+/// alone when the executable itself already exited.
+#[derive(Debug)]
+pub struct ChildGuard {
+    child: Option<Child>,
+}
+
+impl ChildGuard {
+    /// Spawns the command in its own process group on unix, so that dropping the guard kills
+    /// everything it started as well
+    pub fn spawn(command: &mut Command) -> io::Result<ChildGuard> {
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(command, 0);
+        Ok(ChildGuard {
+            child: Some(command.spawn()?),
+        })
+    }
+
+    /// Releases the child without killing it
+    pub fn forget(mut self) -> Child {
+        self.child.take().expect("child is present until dropped")
+    }
+}
+
+impl Deref for ChildGuard {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child.as_ref().expect("child is present until dropped")
+    }
+}
+
+impl DerefMut for ChildGuard {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.child.as_mut().expect("child is present until dropped")
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            // once the child is reaped its pid, which names the group, may already belong to
+            // an unrelated process
+            if let Ok(None) = child.try_wait() {
+                kill_process_tree(child.id());
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll, Waker};
+
+use crate::{BinTest, BinTestBuilder, Error};
+
+/// A build running in the background, created by `BinTestBuilder::spawn_build()`.
+/// With the 'async' feature it can be awaited as well.
+pub struct BuildHandle {
+    thread: Option<JoinHandle<Result<BinTest, Error>>>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    done: bool,
+    #[cfg(feature = "async")]
+    waker: Option<Waker>,
+}
+
+impl BuildHandle {
+    pub(crate) fn spawn(builder: BinTestBuilder) -> BuildHandle {
+        let state = Arc::new(Mutex::new(State::default()));
+        let thread_state = Arc::clone(&state);
+        let thread = std::thread::spawn(move || {
+            let result = builder.try_build();
+            let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.done = true;
+            #[cfg(feature = "async")]
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            result
+        });
+        BuildHandle {
+            thread: Some(thread),
+            state,
+        }
+    }
+
+    /// Blocks until the build finished. Panics when the result was already taken by
+    /// `try_wait()`.
+    pub fn wait(mut self) -> Result<BinTest, Error> {
+        self.join()
+    }
+
+    /// Returns the result when the build finished, `None` while it is still running and
+    /// after the result was taken
+    pub fn try_wait(&mut self) -> Option<Result<BinTest, Error>> {
+        if self.thread.is_some() && self.is_done() {
+            Some(self.join())
+        } else {
+            None
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .done
+    }
+
+    fn join(&mut self) -> Result<BinTest, Error> {
+        let thread = self
+            .thread
+            .take()
+            .expect("bintest: the build result was already taken");
+        thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+#[cfg(feature = "async")]
+impl Future for BuildHandle {
+    type Output = Result<BinTest, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        {
+            // the waker is registered under the same lock the build thread sets 'done' with
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if !state.done {
+                state.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(self.join())
+    }
+}
+
+/// An executable running on a thread of its own, created by `BinTest::run_async()` and
+/// `BinTest::run_async_timeout()`. Awaiting it does not block, it works with any runtime.
+#[cfg(feature = "async")]
+pub struct RunHandle<T> {
+    state: Arc<Mutex<RunState<T>>>,
+}
+
+#[cfg(feature = "async")]
+struct RunState<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + 'static> RunHandle<T> {
+    pub(crate) fn spawn<F: FnOnce() -> T + Send + 'static>(run: F) -> RunHandle<T> {
+        let state = Arc::new(Mutex::new(RunState {
+            result: None,
+            waker: None,
+        }));
+        let thread_state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            let result = run();
+            let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        RunHandle { state }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Future for RunHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
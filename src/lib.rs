@@ -19,14 +19,15 @@
 //! identifying and locating the build executables. On request it creates a std::process::Command
 //! for the executable which can be used for any further testing.
 //!
-//! BinTest will panic on any error and not offer any error handling. This is deliberate to
-//! make things simple.
+//! `BinTest::new()` and `BinTestBuilder::build()` panic on any error to keep simple tests
+//! simple. `BinTest::try_new()` and `BinTestBuilder::try_build()` return an `Error` instead.
 //!
 //!
 //! # Example
 //!
-//! ```rust
-//! #[test]
+//! ```rust,no_run
+//! use bintest::BinTest;
+//!
 //! fn test() {
 //!   // BinTest::new() will run 'cargo build' and registers all build executables
 //!   let executables = BinTest::new();
@@ -37,7 +38,7 @@
 //!   }
 //!
 //!   // BinTest::command() looks up executable by its name and creates a process::Command from it
-//!   let command = executables.command("name");
+//!   let mut command = executables.command("name");
 //!
 //!   // this command can then be used for testing
 //!   command.arg("help").spawn();
@@ -45,83 +46,5809 @@
 //! }
 //! ```
 //!
+//! When the defaults do not fit, the build can be configured with a `BinTestBuilder`:
+//!
+//! ```rust,no_run
+//! use bintest::BinTest;
+//!
+//! let executables = BinTest::with()
+//!     .build_workspace(true)
+//!     .features(["server", "tls"])
+//!     .build();
+//! ```
+//!
 //!
 //! # See Also
 //!
 //! The testcall crate uses this to build tests and assertions on top of the commands created by
 //! bintest.
 //!
-use std::collections::BTreeMap;
+//! The commands work with 'assert_cmd' as well. With the 'assert-cmd' feature
+//! `BinTest::assert_command()` gives an 'assert_cmd::Command' directly:
+//!
+//! ```rust,ignore
+//! let bins = bintest::BinTest::new();
+//! bins.assert_command("cli").arg("--help").assert().success();
+//! ```
+//!
+//! 'assert_cmd::Command::cargo_bin()' finds the executables of the crate under test through
+//! the 'CARGO_BIN_EXE_<name>' variables cargo sets for integration tests, and guesses the
+//! target directory elsewhere. It is enough for bin targets of the tested package. Prefer
+//! bintest for examples, other workspace members, other features or profiles, and whenever
+//! the executables have to be build first.
+//!
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashSet};
 use std::env::var_os as env;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+mod cache;
+mod error;
+mod guard;
+mod handle;
+#[cfg(all(feature = "pty", unix))]
+mod pty;
+mod runner;
+mod snapshot;
+pub use error::{
+    ArtifactProblem, BuildError, ConfigError, Error, InvalidExecutable, LookupError, MergeError,
+    PatternError, TimeoutError, VerifyReport,
+};
+pub use guard::ChildGuard;
+pub use handle::BuildHandle;
+#[cfg(feature = "async")]
+pub use handle::RunHandle;
+#[cfg(all(feature = "pty", unix))]
+pub use pty::{PtyChild, PtyCommand, PtyOutput};
+pub use runner::Runner;
+pub use snapshot::{RegistrySnapshot, SnapshotExecutable};
+
+/// With the 'macros' feature: `#[bintest::test] fn name(bins: &BinTest)` becomes a `#[test]`
+/// which gets a `BinTest` shared by all tests with the same options, e.g.
+/// `#[bintest::test(workspace, features("server"))]`.
+#[cfg(feature = "macros")]
+pub use bintest_macros::test;
 
 pub use std::process::{Command, Stdio};
 
-pub use cargo_metadata::camino::Utf8PathBuf;
-use cargo_metadata::Message;
+pub use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::diagnostic::DiagnosticLevel;
+use cargo_metadata::semver::{Prerelease, Version, VersionReq};
+#[cfg(feature = "artifact")]
+pub use cargo_metadata::Artifact;
+#[cfg(not(feature = "artifact"))]
+use cargo_metadata::Artifact;
+pub use cargo_metadata::BuildScript;
+pub use cargo_metadata::CompilerMessage;
+use cargo_metadata::{Message, Metadata, MetadataCommand, Package, PackageId};
+use fs4::fs_std::FileExt;
 
 /// Access to binaries build by 'cargo build'
 pub struct BinTest {
     build_executables: BTreeMap<String, Utf8PathBuf>,
+    profile: String,
+    target: Option<String>,
+    waited_for_build: bool,
+    failed_targets: Vec<BuildFailure>,
+    build_output: String,
+    // bare names shared by executables of several packages, with their qualified names
+    ambiguous: BTreeMap<String, Vec<String>>,
+    verify_on_command: bool,
+    messages: Vec<CompilerMessage>,
+    rebuilt: bool,
+    // registered names of the executables that had to be compiled
+    rebuilt_executables: HashSet<String>,
+    // removes the target directories of `ephemeral_target_dir()` when dropped, there are
+    // several after `merge()`
+    ephemeral_dirs: Vec<EphemeralDir>,
+    stats: BuildStats,
+    // how often `build_cached()` handed out this instance again
+    cache_hits: AtomicUsize,
+    // what cargo reported about each executable
+    infos: BTreeMap<String, ExecutableInfo>,
+    // the cargo command line, `None` when cargo was not run
+    invocation: Option<CargoInvocation>,
+    // discovered on the first command
+    runner: OnceLock<Option<Runner>>,
+    // the libraries of `include_libraries()` by target name
+    libraries: BTreeMap<String, Utf8PathBuf>,
+    // the debug info cargo listed next to each executable
+    debug_infos: BTreeMap<String, Utf8PathBuf>,
+    // the build script outputs of `collect_build_scripts()` by package name
+    build_scripts: BTreeMap<String, BuildScript>,
+    // the configuration for `rebuild()`
+    builder: BinTestBuilder,
 }
 
-//PLANNED: needs some better way to figure out what profile is active
-#[cfg(not(debug_assertions))]
-const RELEASE_BUILD: bool = true;
+impl std::fmt::Debug for BinTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BinTest")
+            .field("profile", &self.profile)
+            .field("target", &self.target)
+            .field("executables", &self.build_executables)
+            .finish()
+    }
+}
 
-#[cfg(debug_assertions)]
-const RELEASE_BUILD: bool = false;
+/// Verbosity of the build, all but `Silent` print a summary line to stderr after the
+/// executables were registered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// No cargo progress output ('--quiet') and no summary
+    Silent,
+    /// The default cargo output
+    #[default]
+    Normal,
+    /// Verbose cargo output ('-v')
+    Verbose,
+    /// Very verbose cargo output ('-vv')
+    VeryVerbose,
+}
 
-impl BinTest {
-    /// Runs 'cargo build' and register all build executables.
-    /// Executables are identified by their name, without path and filename extension.
-    pub fn new() -> BinTest {
-        let mut cargo_build = Command::new(env("CARGO").unwrap_or_else(|| OsString::from("cargo")));
+/// Whether cargo output and collected diagnostics use colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Let cargo decide based on the terminal ('--color auto')
+    Auto,
+    /// Always use colors ('--color always')
+    Always,
+    /// Never use colors ('--color never')
+    Never,
+}
 
-        cargo_build
-            .args(["build", "--message-format", "json"])
-            .stdout(Stdio::piped());
+/// A sanitizer the executables are build with, see `BinTestBuilder::sanitizer()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sanitizer {
+    /// AddressSanitizer, finds out of bounds accesses and use after free
+    Address,
+    /// LeakSanitizer, finds memory leaks
+    Leak,
+    /// MemorySanitizer, finds reads of uninitialized memory, builds the standard library
+    Memory,
+    /// ThreadSanitizer, finds data races, builds the standard library
+    Thread,
+    /// UndefinedBehaviorSanitizer for the C and C++ code the 'cc' crate builds. rustc has no
+    /// such sanitizer for Rust code, Miri covers that. Works on stable toolchains.
+    Undefined,
+}
+
+impl Sanitizer {
+    /// The exit code of an executable that reported an error, the sanitizers are configured
+    /// to use it through their '*SAN_OPTIONS' variable
+    pub const EXIT_CODE: i32 = 86;
+
+    /// Returns true when the executable exited because the sanitizer reported an error
+    pub fn reported(status: &std::process::ExitStatus) -> bool {
+        status.code() == Some(Sanitizer::EXIT_CODE)
+    }
+
+    /// The name of the sanitizer, for '-Zsanitizer=name' of rustc and '-fsanitize=name' of C
+    /// compilers
+    pub fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Leak => "leak",
+            Sanitizer::Memory => "memory",
+            Sanitizer::Thread => "thread",
+            Sanitizer::Undefined => "undefined",
+        }
+    }
 
-        if RELEASE_BUILD {
-            cargo_build.arg("--release");
+    /// The variable with the runtime options of the sanitizer
+    fn options_variable(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "ASAN_OPTIONS",
+            Sanitizer::Leak => "LSAN_OPTIONS",
+            Sanitizer::Memory => "MSAN_OPTIONS",
+            Sanitizer::Thread => "TSAN_OPTIONS",
+            Sanitizer::Undefined => "UBSAN_OPTIONS",
         }
+    }
 
-        let mut cargo_result = cargo_build.spawn().expect("'cargo build' success");
+    /// The options for the executables, the ones from the environment with the exit code
+    fn options(self) -> OsString {
+        let mut options = env(self.options_variable()).unwrap_or_default();
+        if !options.is_empty() {
+            options.push(":");
+        }
+        options.push(format!("exitcode={}", Sanitizer::EXIT_CODE));
+        if self == Sanitizer::Undefined {
+            // reported errors are only printed otherwise
+            options.push(":halt_on_error=1");
+        }
+        options
+    }
+}
 
-        let mut build_executables = BTreeMap::new();
+/// A target that failed to build
+#[derive(Debug, Clone)]
+pub struct BuildFailure {
+    /// Name of the target as given in 'Cargo.toml'
+    pub target: String,
+    /// The rendered compiler errors for this target, may contain ANSI colors
+    pub diagnostics: String,
+}
 
-        let reader = std::io::BufReader::new(cargo_result.stdout.take().unwrap());
-        for message in cargo_metadata::Message::parse_stream(reader) {
-            if let Message::CompilerArtifact(artifact) = message.unwrap() {
-                if let Some(executable) = artifact.executable {
-                    build_executables.insert(
-                        String::from(executable.file_stem().expect("filename")),
-                        executable.to_path_buf(),
-                    );
-                }
+impl BuildFailure {
+    /// Returns the rendered compiler errors with ANSI escape sequences removed
+    pub fn diagnostics_plain(&self) -> String {
+        strip_ansi(&self.diagnostics)
+    }
+}
+
+/// One entry of `BinTestBuilder::matrix()`, a set of features and the label its executables
+/// are registered with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureSet {
+    /// Suffix of the registered names, 'name@label'
+    pub label: String,
+    /// The features enabled in addition to the ones of the builder
+    pub features: Vec<String>,
+}
+
+impl FeatureSet {
+    /// Creates a feature set with the given label
+    pub fn new<L: Into<String>, I: IntoIterator<Item = S>, S: Into<String>>(
+        label: L,
+        features: I,
+    ) -> FeatureSet {
+        FeatureSet {
+            label: label.into(),
+            features: features.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// A registered executable, returned by `BinTest::executable()` and `BinTest::get()`
+#[derive(Clone, Copy)]
+pub struct Executable<'a> {
+    bintest: &'a BinTest,
+    name: &'a str,
+    path: &'a Utf8Path,
+}
+
+impl std::fmt::Debug for Executable<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Executable")
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl<'a> Executable<'a> {
+    /// The name the executable is registered with
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The path cargo reported for the executable
+    pub fn path(&self) -> &'a Utf8Path {
+        self.path
+    }
+
+    /// The name of the package the executable belongs to, when known
+    pub fn package(&self) -> Option<&'a str> {
+        self.bintest
+            .infos
+            .get(self.name)
+            .map(|info| info.package.as_str())
+    }
+
+    /// Constructs a 'std::process::Command' for the executable, same as `BinTest::command()`
+    pub fn command(&self) -> Command {
+        self.bintest.command(self.name)
+    }
+
+    /// Runs the executable with the given arguments and collects its output
+    pub fn run<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        args: I,
+    ) -> std::io::Result<std::process::Output> {
+        self.command().args(args).output()
+    }
+
+    /// Runs the executable with the given arguments and returns its stdout, same as
+    /// `BinTest::run_ok()`
+    #[track_caller]
+    pub fn run_ok<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(&self, args: I) -> String {
+        run_successfully(self.command().args(args))
+    }
+}
+
+/// What cargo reported about a registered executable, returned by `BinTest::info()`
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ExecutableInfo {
+    /// Id of the package the executable belongs to
+    pub package_id: String,
+    /// Name of the package the executable belongs to
+    pub package: String,
+    /// Kind of the target, one of 'bin', 'example', 'test' or 'bench'
+    pub kind: String,
+    /// The features the package was build with, empty when they are not known like for
+    /// executables taken from 'CARGO_BIN_EXE_<name>'
+    pub features: Vec<String>,
+    /// Name of the profile the executable was build with
+    pub profile: String,
+    /// The message cargo emitted for the executable, `None` when it was not registered from
+    /// a cargo build
+    #[cfg(feature = "artifact")]
+    pub artifact: Option<Artifact>,
+}
+
+/// How `BinTest::merge_with()` handles names registered in both with different paths
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Fail with a `MergeError`
+    #[default]
+    Fail,
+    /// Keep the executable of `self`
+    KeepFirst,
+    /// Keep the executable of `other`
+    KeepLast,
+    /// Register both as 'package/name' like `BinTestBuilder::qualify_duplicates()`, fails
+    /// when their packages are unknown or the same and for libraries and build scripts
+    Qualify,
+}
+
+/// A directory tests run executables in, see `BinTest::command_in()`. Implemented for
+/// everything that is `AsRef<Path>`, crates providing temporary test directories can
+/// implement it for their own types.
+pub trait TestDir {
+    /// Returns the path of the directory
+    fn test_dir(&self) -> &Path;
+}
+
+impl<P: AsRef<Path> + ?Sized> TestDir for P {
+    fn test_dir(&self) -> &Path {
+        self.as_ref()
+    }
+}
+
+/// What changed by `BinTest::rebuild()`
+#[derive(Debug, Clone, Default)]
+pub struct RebuildReport {
+    /// Executables that were compiled again
+    pub recompiled: Vec<String>,
+    /// Executables that are registered with a different path now
+    pub moved: Vec<String>,
+}
+
+/// Where the executables of a `BinTest` came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildSource {
+    /// 'cargo build' was run
+    Cargo,
+    /// Loaded from the `registry_cache()` without running 'cargo build'
+    DiskCache,
+    /// Taken from the 'CARGO_BIN_EXE_<name>' variables without running cargo
+    BinExeEnv,
+    /// Restored by `BinTest::from_snapshot()`
+    Snapshot,
+    /// Given to `BinTest::from_paths()`
+    Paths,
+}
+
+/// Timing and artifact counts of a build, see `BinTest::build_stats()`
+#[derive(Debug, Clone, Copy)]
+pub struct BuildStats {
+    /// Where the executables came from
+    pub source: BuildSource,
+    /// Wall clock time of the cargo invocation, zero when cargo was not run
+    pub duration: Duration,
+    /// Number of artifacts that had to be compiled
+    pub compiled: usize,
+    /// Number of artifacts that were up to date
+    pub fresh: usize,
+    /// How often `build_cached()` returned this build again without building
+    pub cache_hits: usize,
+}
+
+impl std::fmt::Display for BuildStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.source {
+            BuildSource::Cargo => write!(
+                f,
+                "'cargo build' took {:.1}s, {} artifacts compiled, {} fresh",
+                self.duration.as_secs_f64(),
+                self.compiled,
+                self.fresh
+            )?,
+            BuildSource::DiskCache => write!(f, "loaded from the registry cache")?,
+            BuildSource::BinExeEnv => write!(f, "taken from CARGO_BIN_EXE_*")?,
+            BuildSource::Snapshot => write!(f, "restored from a snapshot")?,
+            BuildSource::Paths => write!(f, "registered from known paths")?,
+        }
+        if self.cache_hits > 0 {
+            write!(f, ", reused {} times", self.cache_hits)?;
+        }
+        Ok(())
+    }
+}
+
+/// The cargo command line of a build, see `BinTest::cargo_invocation()` and
+/// `BinTestBuilder::dry_run()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CargoInvocation {
+    /// The cargo executable
+    pub program: OsString,
+    /// All arguments passed to cargo
+    pub args: Vec<OsString>,
+    /// Environment variables set for cargo, `None` for removed ones
+    pub env: Vec<(OsString, Option<OsString>)>,
+}
+
+impl CargoInvocation {
+    fn from_command(command: &Command) -> CargoInvocation {
+        CargoInvocation {
+            program: command.get_program().to_owned(),
+            args: command.get_args().map(OsStr::to_owned).collect(),
+            env: command
+                .get_envs()
+                .map(|(key, value)| (key.to_owned(), value.map(OsStr::to_owned)))
+                .collect(),
+        }
+    }
+}
+
+/// Prints the invocation as a shell command line that can be pasted into a terminal
+impl std::fmt::Display for CargoInvocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut words = Vec::new();
+        if self.env.iter().any(|(_, value)| value.is_none()) {
+            words.push(String::from("env"));
+            for (key, _) in self.env.iter().filter(|(_, value)| value.is_none()) {
+                words.push(String::from("-u"));
+                words.push(shell_quote(key));
+            }
+        }
+        for (key, value) in &self.env {
+            if let Some(value) = value {
+                words.push(format!("{}={}", key.to_string_lossy(), shell_quote(value)));
             }
         }
+        words.push(shell_quote(&self.program));
+        words.extend(self.args.iter().map(|arg| shell_quote(arg)));
+        write!(f, "{}", words.join(" "))
+    }
+}
 
-        BinTest { build_executables }
+/// Quotes a word for a POSIX shell when it contains anything but safe characters
+fn shell_quote(word: &OsStr) -> String {
+    let word = word.to_string_lossy();
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        word.into_owned()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
     }
+}
 
-    /// Gives an `(name, path)` iterator over all executables found
-    pub fn list_executables(&self) -> std::collections::btree_map::Iter<'_, String, Utf8PathBuf> {
-        self.build_executables.iter()
+/// Overview of a `BinTest`, returned by `BinTest::summary()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildSummary {
+    /// Name of the profile the executables were build with
+    pub profile: String,
+    /// The target triple when cross compiling
+    pub target: Option<String>,
+    /// Number of registered executables
+    pub executables: usize,
+    /// Size of all registered executables in bytes, files registered under several names
+    /// are counted once
+    pub total_size: u64,
+    /// Wall clock time of the cargo invocation, zero when cargo was not run
+    pub duration: Duration,
+}
+
+impl std::fmt::Display for BuildSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} executables ({:.1} MiB), profile '{}'",
+            self.executables,
+            self.total_size as f64 / (1024.0 * 1024.0),
+            self.profile
+        )?;
+        if let Some(target) = &self.target {
+            write!(f, ", target '{}'", target)?;
+        }
+        write!(f, ", build in {:.1}s", self.duration.as_secs_f64())
     }
+}
 
-    /// Constructs a 'std::process::Command' for the given executable name
-    pub fn command(&self, name: &str) -> Command {
-        Command::new(
-            self.build_executables
-                .get(name)
-                .unwrap_or_else(|| panic!("no such executable <<{}>>", name)),
-        )
+/// A registered executable with its path in a machine independent form, returned by
+/// `BinTest::normalized_entries()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ListingEntry {
+    /// The name the executable is registered with
+    pub name: String,
+    /// The path below the profile directory with '/' as separator, without '.exe' and the
+    /// hash cargo adds to files in 'deps/', like 'examples/demo'
+    pub path: String,
+}
+
+impl std::fmt::Display for ListingEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.path)
     }
 }
 
-impl Default for BinTest {
-    fn default() -> Self {
-        Self::new()
+/// Removes ANSI escape sequences (colors, cursor movement, hyperlinks) from the given text
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates up to a final byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ESC '\\'
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // two character sequences
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// Path and contents of 'Cargo.lock' before the build, the contents are `None` when it did
+/// not exist
+type LockfileSnapshot = (Utf8PathBuf, Option<Vec<u8>>);
+
+/// Configures how 'cargo build' is invoked before the executables are registered
+#[derive(Debug, Default, Clone)]
+pub struct BinTestBuilder {
+    build_workspace: bool,
+    specific_executables: Vec<String>,
+    verbosity: Verbosity,
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
+    profile: Option<String>,
+    release: Option<bool>,
+    target: Option<String>,
+    manifest_path: Option<Utf8PathBuf>,
+    packages: Vec<String>,
+    excludes: Vec<String>,
+    examples: Vec<String>,
+    all_examples: bool,
+    build_tests: bool,
+    build_benches: bool,
+    cargo_args: Vec<OsString>,
+    cargo_env: Vec<(OsString, Option<OsString>)>,
+    rustflags: Vec<String>,
+    replace_rustflags: bool,
+    offline: bool,
+    locked: bool,
+    frozen: bool,
+    target_dir: Option<Utf8PathBuf>,
+    jobs: Option<usize>,
+    toolchain: Option<String>,
+    cargo_config: Vec<String>,
+    unstable_flags: Vec<String>,
+    allow_unstable_on_stable: bool,
+    keep_going: bool,
+    ignore_rust_version: bool,
+    no_required_features: bool,
+    all_bins: bool,
+    cargo_command: Option<OsString>,
+    build_subcommand: Option<OsString>,
+    color: Option<ColorChoice>,
+    capture_stderr: bool,
+    skip_target_check: bool,
+    qualify_duplicates: bool,
+    timeout: Option<Duration>,
+    allow_empty: bool,
+    cross_process_lock: Option<Duration>,
+    expect_fresh: bool,
+    ephemeral_target_dir: bool,
+    registry_cache: bool,
+    force_rebuild: bool,
+    no_bin_exe_env: bool,
+    matrix: Vec<FeatureSet>,
+    verify_on_command: bool,
+    fail_on_warnings: bool,
+    collect_messages: bool,
+    sanitize_env: bool,
+    command_env: Vec<(OsString, Option<OsString>)>,
+    register_patterns: Vec<String>,
+    match_ignore_case: bool,
+    excluded_executables: Vec<String>,
+    lenient_lookup: Option<bool>,
+    runner: Option<Runner>,
+    use_cross: bool,
+    wasm_dirs: Vec<PathBuf>,
+    wasm_env: Vec<String>,
+    coverage: Option<bool>,
+    sanitizer: Option<Sanitizer>,
+    bench_target_dir: Option<bool>,
+    include_libraries: bool,
+    collect_build_scripts: bool,
+    dependency_artifacts: bool,
+}
+
+impl BinTestBuilder {
+    /// Creates a builder with the default settings, same as `BinTest::new()` uses
+    pub fn new() -> BinTestBuilder {
+        BinTestBuilder::default()
+    }
+
+    /// Builds all executables in the workspace ('--workspace')
+    pub fn build_workspace(mut self, workspace: bool) -> Self {
+        self.build_workspace = workspace;
+        self
+    }
+
+    /// Builds only the given executable ('--bin name').
+    /// Can be called multiple times to build more executables.
+    pub fn build_executable<S: Into<String>>(mut self, executable: S) -> Self {
+        self.specific_executables.push(executable.into());
+        self
+    }
+
+    /// Builds only the given executables, same as calling `build_executable()` for each
+    pub fn build_executables<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        executables: I,
+    ) -> Self {
+        self.specific_executables
+            .extend(executables.into_iter().map(Into::into));
+        self
+    }
+
+    /// Suppresses the cargo progress output ('--quiet'), same as `verbosity(Verbosity::Silent)`
+    pub fn quiet(self, quiet: bool) -> Self {
+        self.verbosity(if quiet {
+            Verbosity::Silent
+        } else {
+            Verbosity::Normal
+        })
+    }
+
+    /// Controls colored cargo output ('--color'). With `ColorChoice::Always` the diagnostics
+    /// bintest collects are rendered with ANSI colors as well.
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Captures the stderr output of cargo instead of passing it through. It is only shown
+    /// when the build fails, otherwise it is available from `BinTest::build_output()`.
+    pub fn capture_stderr(mut self, capture: bool) -> Self {
+        self.capture_stderr = capture;
+        self
+    }
+
+    /// Sets how verbose cargo and bintest itself are
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Enables the given cargo features ('--features a,b,c').
+    /// Calling this more than once accumulates the features.
+    pub fn features<I: IntoIterator<Item = S>, S: Into<String>>(mut self, features: I) -> Self {
+        self.features.extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Builds once for every feature set, each into its own target directory below the
+    /// target directory. The executables are registered as 'name@label', for example
+    /// 'cli@backend-a'.
+    pub fn matrix(mut self, sets: &[FeatureSet]) -> Self {
+        self.matrix.extend_from_slice(sets);
+        self
+    }
+
+    /// Builds every entry of the `matrix()` and registers them under their labels
+    fn build_matrix(self) -> Result<BinTest, Error> {
+        let metadata = OnceCell::new();
+        let target_dir = self
+            .target_directory(&metadata)?
+            .ok_or_else(|| {
+                Error::InvalidConfig(ConfigError {
+                    conflicts: vec![String::from(
+                        "matrix() needs a target directory that can be resolved",
+                    )],
+                })
+            })?
+            .join("bintest-matrix");
+
+        let mut builds = Vec::new();
+        for set in &self.matrix {
+            let mut builder = self.clone();
+            builder.matrix.clear();
+            builder.features.extend(set.features.iter().cloned());
+            builder.target_dir = Some(target_dir.join(&set.label));
+            builds.push((set.label.clone(), builder.try_build()?));
+        }
+        Ok(BinTest::from_matrix(builds, self))
+    }
+
+    /// Activates all available features ('--all-features')
+    pub fn all_features(mut self, all: bool) -> Self {
+        self.all_features = all;
+        self
+    }
+
+    /// Disables the default features ('--no-default-features')
+    pub fn no_default_features(mut self, no_default: bool) -> Self {
+        self.no_default_features = no_default;
+        self
+    }
+
+    /// Builds with the given cargo profile ('--profile name').
+    /// This overrides the default release/debug selection.
+    pub fn profile<S: Into<String>>(mut self, name: S) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    /// Explicitly selects a release ('--release') or debug build. This takes precedence over
+    /// the profile detected from the running test executable, but a `profile()`
+    /// takes precedence over this.
+    pub fn release(mut self, release: bool) -> Self {
+        self.release = Some(release);
+        self
+    }
+
+    /// Explicitly selects a debug build, same as `release(!debug)`
+    pub fn debug(self, debug: bool) -> Self {
+        self.release(!debug)
+    }
+
+    /// Builds for the given target triple ('--target triple'). Whether the resulting
+    /// executables can be run on the host is up to the user.
+    pub fn target<S: Into<String>>(mut self, triple: S) -> Self {
+        self.target = Some(triple.into());
+        self
+    }
+
+    /// Starts the executables with the given program, the path of the executable is passed
+    /// after `args`. Without this the runner is taken from the cargo configuration for the
+    /// target, see `BinTest::runner()`.
+    pub fn runner<P, I, A>(mut self, program: P, args: I) -> Self
+    where
+        P: Into<OsString>,
+        I: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        self.runner = Some(Runner::new(program, args));
+        self
+    }
+
+    /// Gives the WebAssembly executables access to the given directory, instead of the
+    /// current one. Can be called multiple times, see `BinTest::runner()`.
+    pub fn wasm_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.wasm_dirs.push(dir.into());
+        self
+    }
+
+    /// Passes the given environment variable on to the WebAssembly executables, they see
+    /// none by default. Can be called multiple times.
+    pub fn wasm_env<S: Into<String>>(mut self, key: S) -> Self {
+        self.wasm_env.push(key.into());
+        self
+    }
+
+    /// The 'wasmtime' runner with the directories of `wasm_dir()` and the variables of
+    /// `wasm_env()`
+    fn wasm_runner(&self) -> Runner {
+        let mut args = vec![OsString::from("run")];
+        let current = [PathBuf::from(".")];
+        let dirs = if self.wasm_dirs.is_empty() {
+            &current[..]
+        } else {
+            &self.wasm_dirs
+        };
+        for dir in dirs {
+            args.push(OsString::from("--dir"));
+            args.push(dir.clone().into_os_string());
+        }
+        for key in &self.wasm_env {
+            args.push(OsString::from("--env"));
+            args.push(OsString::from(key));
+        }
+        args.push(OsString::from("--"));
+        Runner::new("wasmtime", args)
+    }
+
+    /// Starts the executables under 'wine', for testing Windows builds like
+    /// `target("x86_64-pc-windows-gnu")` on unix, see `Runner::wine()`. Building fails with
+    /// `Error::RunnerNotFound` when wine is not installed.
+    pub fn run_under_wine(mut self, wine: bool) -> Self {
+        self.runner = wine.then(Runner::wine);
+        self
+    }
+
+    /// Builds the package described by the given 'Cargo.toml' ('--manifest-path path').
+    /// Relative paths are resolved against the 'CARGO_MANIFEST_DIR' of the calling crate.
+    pub fn manifest_path<P: Into<Utf8PathBuf>>(mut self, path: P) -> Self {
+        self.manifest_path = Some(path.into());
+        self
+    }
+
+    /// Resolves the manifest path and checks that it exists
+    fn resolved_manifest_path(&self) -> Result<Option<Utf8PathBuf>, Error> {
+        let path = match &self.manifest_path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let path = match env("CARGO_MANIFEST_DIR").and_then(|dir| dir.into_string().ok()) {
+            Some(dir) if path.is_relative() => Utf8PathBuf::from(dir).join(path),
+            _ => path.clone(),
+        };
+        if path.is_file() {
+            Ok(Some(path))
+        } else {
+            Err(Error::ManifestNotFound(path))
+        }
+    }
+
+    /// Restricts the build to the given workspace member ('-p pkg').
+    /// Can be called multiple times to select more packages.
+    pub fn package<S: Into<String>>(mut self, pkg: S) -> Self {
+        self.packages.push(pkg.into());
+        self
+    }
+
+    /// Excludes the given package from a workspace build ('--exclude pkg').
+    /// Can be called multiple times, only valid together with `build_workspace(true)`.
+    pub fn exclude<S: Into<String>>(mut self, pkg: S) -> Self {
+        self.excludes.push(pkg.into());
+        self
+    }
+
+    /// Builds all binary targets only ('--bins'), composes with `build_workspace()` and
+    /// `package()`
+    pub fn all_bins(mut self, bins: bool) -> Self {
+        self.all_bins = bins;
+        self
+    }
+
+    /// Builds the given example ('--example name'), can be called multiple times.
+    /// Examples are registered as 'example/name' and under their bare name unless a
+    /// binary with the same name exists.
+    pub fn build_example<S: Into<String>>(mut self, name: S) -> Self {
+        self.examples.push(name.into());
+        self
+    }
+
+    /// Builds all examples ('--examples'), registered like in `build_example()`
+    pub fn build_examples(mut self, examples: bool) -> Self {
+        self.all_examples = examples;
+        self
+    }
+
+    /// Builds the test harness executables as well ('--tests'). These are registered by their
+    /// target name without the hash suffix as 'test/name' and under their bare name unless
+    /// another executable with the same name exists.
+    pub fn build_tests(mut self, tests: bool) -> Self {
+        self.build_tests = tests;
+        self
+    }
+
+    /// Builds the bench target executables as well ('--benches'), registered as 'bench/name'
+    /// like in `build_tests()`. They are build with the profile selected for the build, use
+    /// `profile("bench")` to get the profile 'cargo bench' would use.
+    pub fn build_benches(mut self, benches: bool) -> Self {
+        self.build_benches = benches;
+        self
+    }
+
+    /// Appends a raw argument to the 'cargo build' command line. These are passed in the order
+    /// given after all arguments bintest generates itself. A '--message-format' argument is
+    /// dropped since bintest depends on the JSON output.
+    pub fn cargo_arg<S: Into<OsString>>(mut self, arg: S) -> Self {
+        self.cargo_args.push(arg.into());
+        self
+    }
+
+    /// Appends multiple raw arguments, same as calling `cargo_arg()` for each
+    pub fn cargo_args<I: IntoIterator<Item = S>, S: Into<OsString>>(mut self, args: I) -> Self {
+        self.cargo_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets an environment variable for the 'cargo build' process. This does not affect the
+    /// commands created by `BinTest::command()`.
+    pub fn env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, value: V) -> Self {
+        self.cargo_env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Removes an environment variable for the 'cargo build' process
+    pub fn env_remove<K: Into<OsString>>(mut self, key: K) -> Self {
+        self.cargo_env.push((key.into(), None));
+        self
+    }
+
+    /// Sets an environment variable for every command created by `BinTest::command()`.
+    /// Calling `env()` on the command overrides it.
+    pub fn command_env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, value: V) -> Self {
+        self.command_env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Removes an environment variable for every command created by `BinTest::command()`
+    pub fn command_env_remove<K: Into<OsString>>(mut self, key: K) -> Self {
+        self.command_env.push((key.into(), None));
+        self
+    }
+
+    /// Adds a single flag passed to rustc ('-C target-cpu=native' has to be given as two flags)
+    pub fn rustflag<S: Into<String>>(mut self, flag: S) -> Self {
+        self.rustflags.push(flag.into());
+        self
+    }
+
+    /// Adds whitespace separated flags passed to rustc, like the 'RUSTFLAGS' variable.
+    /// They are merged with any 'RUSTFLAGS' already in the environment unless
+    /// `replace_rustflags(true)` is set.
+    pub fn rustflags<S: Into<String>>(mut self, flags: S) -> Self {
+        self.rustflags
+            .extend(flags.into().split_whitespace().map(String::from));
+        self
+    }
+
+    /// Replaces the rustflags from the environment instead of appending to them
+    pub fn replace_rustflags(mut self, replace: bool) -> Self {
+        self.replace_rustflags = replace;
+        self
+    }
+
+    /// Builds the executables with the given sanitizer, which needs a nightly toolchain
+    /// except for `Sanitizer::Undefined`. Building fails with `Error::NightlyRequired` right
+    /// away on other toolchains. The build is for an explicit target, the host unless
+    /// `target()` is given, so that build scripts are not instrumented. The commands get the
+    /// sanitizer options with `Sanitizer::EXIT_CODE` as exit code for reported errors.
+    pub fn sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = Some(sanitizer);
+        self
+    }
+
+    /// Resolves the settings which depend on the environment before building, shared by
+    /// `try_build()` and `dry_run()` so that both give the same command
+    fn prepare(&mut self) -> Result<(), Error> {
+        self.prepare_sanitizer()?;
+        if let Some(dir) = self.resolved_bench_target_dir() {
+            self.target_dir = Some(dir);
+        }
+        Ok(())
+    }
+
+    /// Checks for a nightly toolchain and sets the target to the host when the sanitizer
+    /// needs one
+    fn prepare_sanitizer(&mut self) -> Result<(), Error> {
+        let Some(sanitizer) = self.sanitizer else {
+            return Ok(());
+        };
+        let version = self.rustc_version_info().unwrap_or_default();
+        let release = version
+            .lines()
+            .find_map(|line| line.strip_prefix("release: "))
+            .unwrap_or("unknown")
+            .trim();
+        if sanitizer != Sanitizer::Undefined
+            && !release.contains("nightly")
+            && !release.contains("dev")
+            && env("RUSTC_BOOTSTRAP").is_none()
+        {
+            return Err(Error::NightlyRequired {
+                feature: format!("sanitizer <<{}>>", sanitizer.name()),
+                found: String::from(release),
+            });
+        }
+        if self.target.is_none() {
+            self.target = version
+                .lines()
+                .find_map(|line| line.strip_prefix("host: "))
+                .map(|host| String::from(host.trim()));
+        }
+        Ok(())
+    }
+
+    /// Returns the output of 'rustc -vV' for the configured toolchain
+    fn rustc_version_info(&self) -> Option<String> {
+        let mut rustc = Command::new(env("RUSTC").unwrap_or_else(|| OsString::from("rustc")));
+        if let Some(toolchain) = &self.toolchain {
+            rustc.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        let output = rustc.arg("-vV").output().ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Builds the executables with coverage instrumentation ('-C instrument-coverage') and
+    /// makes them write their profile to 'LLVM_PROFILE_FILE', one file per process. Enabled
+    /// by default when running under 'cargo llvm-cov'. The commands get 'LLVM_PROFILE_FILE'
+    /// only, the rustflags matter to cargo alone.
+    pub fn inherit_coverage(mut self, coverage: bool) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    fn resolved_coverage(&self) -> bool {
+        self.coverage
+            .unwrap_or_else(|| env("CARGO_LLVM_COV").is_some())
+    }
+
+    /// The 'LLVM_PROFILE_FILE' for coverage, the one from the environment with a '%p'
+    /// added when it has none, so that every process writes its own profile
+    fn coverage_profile_file(&self) -> Option<OsString> {
+        if !self.resolved_coverage() {
+            return None;
+        }
+        let file = match env("LLVM_PROFILE_FILE") {
+            Some(file) => file.to_string_lossy().into_owned(),
+            // absolute, commands may run in other directories
+            None => {
+                let dir = std::env::current_dir().unwrap_or_default();
+                return Some(dir.join("bintest-%p-%m.profraw").into_os_string());
+            }
+        };
+        if file.contains("%p") {
+            return Some(OsString::from(file));
+        }
+        Some(OsString::from(match file.strip_suffix(".profraw") {
+            Some(stem) => format!("{}-%p.profraw", stem),
+            None => format!("{}-%p", file),
+        }))
+    }
+
+    /// Computes the 'CARGO_ENCODED_RUSTFLAGS' for the build, `None` when nothing was configured
+    fn encoded_rustflags(&self) -> Option<String> {
+        let instrument =
+            self.resolved_coverage() && !self.env_rustflags().contains("instrument-coverage");
+        if self.rustflags.is_empty() && !instrument && self.sanitizer.is_none() {
+            return None;
+        }
+
+        let mut flags: Vec<String> = Vec::new();
+        if !self.replace_rustflags {
+            // cargo prefers the encoded variant over 'RUSTFLAGS' as well
+            if let Some(encoded) = env("CARGO_ENCODED_RUSTFLAGS") {
+                flags.extend(
+                    encoded
+                        .to_string_lossy()
+                        .split('\x1f')
+                        .filter(|flag| !flag.is_empty())
+                        .map(String::from),
+                );
+            } else if let Some(rustflags) = env("RUSTFLAGS") {
+                flags.extend(
+                    rustflags
+                        .to_string_lossy()
+                        .split_whitespace()
+                        .map(String::from),
+                );
+            }
+        }
+        flags.extend(self.rustflags.iter().cloned());
+        if instrument
+            && !flags
+                .iter()
+                .any(|flag| flag.contains("instrument-coverage"))
+        {
+            flags.extend([String::from("-C"), String::from("instrument-coverage")]);
+        }
+        match self.sanitizer {
+            Some(Sanitizer::Undefined) => {
+                // links the runtime for the instrumented C code, gcc leaves it out as rustc
+                // links with '-nodefaultlibs'
+                flags.push(String::from("-Clink-arg=-fsanitize=undefined"));
+                if linker_is_gcc() {
+                    flags.push(String::from("-Clink-arg=-lubsan"));
+                }
+            }
+            Some(sanitizer) => flags.push(format!("-Zsanitizer={}", sanitizer.name())),
+            None => {}
+        }
+
+        Some(flags.join("\x1f"))
+    }
+
+    /// The rustflags from the environment, unsplit
+    fn env_rustflags(&self) -> String {
+        env("CARGO_ENCODED_RUSTFLAGS")
+            .or_else(|| env("RUSTFLAGS"))
+            .map(|flags| flags.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Builds without accessing the network ('--offline'). When the build fails because
+    /// dependencies are not available locally this is reported explicitly.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Requires 'Cargo.lock' to be up to date ('--locked'). Additionally bintest verifies that
+    /// the lockfile was not modified by the build.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Requires 'Cargo.lock' and the dependency cache to be up to date ('--frozen'),
+    /// verified like in `locked()`.
+    pub fn frozen(mut self, frozen: bool) -> Self {
+        self.frozen = frozen;
+        self
+    }
+
+    /// Builds into the given directory ('--target-dir dir') instead of the default target
+    /// directory. This avoids waiting on the lock the outer 'cargo test' holds.
+    pub fn target_dir<P: Into<Utf8PathBuf>>(mut self, dir: P) -> Self {
+        self.target_dir = Some(dir.into());
+        self
+    }
+
+    /// Builds into a new temporary target directory which is removed again when the
+    /// `BinTest` is dropped, unless `BinTest::keep()` was called. Keeps builds with unusual
+    /// flags from invalidating the artifacts in the main target directory. The shared builds
+    /// of `build_cached()` and `global()` are never dropped and reject this.
+    pub fn ephemeral_target_dir(mut self) -> Self {
+        self.ephemeral_target_dir = true;
+        self
+    }
+
+    /// Disables the shortcut which registers the executables from the 'CARGO_BIN_EXE_<name>'
+    /// variables cargo sets for integration tests instead of building them again.
+    ///
+    /// The shortcut is only taken when the executables cargo build for the test are the ones
+    /// the builder would build: no workspace, package, feature, profile, target, flag or
+    /// environment settings are given, only `build_executable()`, `all_bins()` and options
+    /// that don't change the build like `quiet()` or `timeout()`. All requested executables
+    /// must exist. Packages with binaries that have 'required-features' beyond the default
+    /// features are always build, cargo sets the variables even when they were not build.
+    /// `ExecutableInfo::features` is empty for the executables taken from the variables.
+    pub fn use_bin_exe_env(mut self, enable: bool) -> Self {
+        self.no_bin_exe_env = !enable;
+        self
+    }
+
+    /// Registers the executables from 'CARGO_BIN_EXE_<name>' when that gives the same result
+    /// as building them, see `use_bin_exe_env()`
+    fn bin_exe_env(&self, metadata: &OnceCell<Metadata>) -> Option<BTreeMap<String, Utf8PathBuf>> {
+        if self.no_bin_exe_env || self.affects_build() {
+            return None;
+        }
+        let package = self.metadata(metadata).ok()?.root_package()?;
+        self.bin_exe_executables(package, std::env::vars())
+    }
+
+    /// Returns true when an option changes what cargo builds, or needs the output of cargo,
+    /// so that the executables cargo build for the test can not be taken
+    fn affects_build(&self) -> bool {
+        // without '..', every new option has to be decided on here
+        let BinTestBuilder {
+            build_workspace,
+            features,
+            all_features,
+            no_default_features,
+            profile,
+            release,
+            target,
+            manifest_path,
+            packages,
+            excludes,
+            examples,
+            all_examples,
+            build_tests,
+            build_benches,
+            cargo_args,
+            cargo_env,
+            rustflags,
+            replace_rustflags,
+            offline,
+            locked,
+            frozen,
+            target_dir,
+            toolchain,
+            cargo_config,
+            unstable_flags,
+            allow_unstable_on_stable,
+            ignore_rust_version,
+            no_required_features,
+            cargo_command,
+            build_subcommand,
+            expect_fresh,
+            ephemeral_target_dir,
+            registry_cache,
+            force_rebuild,
+            matrix,
+            fail_on_warnings,
+            collect_messages,
+            use_cross,
+            sanitizer,
+            bench_target_dir,
+            include_libraries,
+            collect_build_scripts,
+            dependency_artifacts,
+            // these leave the build alone
+            specific_executables: _,
+            all_bins: _,
+            verbosity: _,
+            color: _,
+            capture_stderr: _,
+            skip_target_check: _,
+            qualify_duplicates: _,
+            timeout: _,
+            allow_empty: _,
+            cross_process_lock: _,
+            no_bin_exe_env: _,
+            verify_on_command: _,
+            sanitize_env: _,
+            command_env: _,
+            register_patterns: _,
+            match_ignore_case: _,
+            excluded_executables: _,
+            lenient_lookup: _,
+            runner: _,
+            wasm_dirs: _,
+            wasm_env: _,
+            jobs: _,
+            keep_going: _,
+            // the executables cargo build for the test are instrumented the same way
+            coverage: _,
+        } = self;
+
+        *build_workspace
+            || !features.is_empty()
+            || *all_features
+            || *no_default_features
+            || profile.is_some()
+            || release.is_some()
+            || target.is_some()
+            || manifest_path.is_some()
+            || !packages.is_empty()
+            || !excludes.is_empty()
+            || !examples.is_empty()
+            || *all_examples
+            || *build_tests
+            || *build_benches
+            || !cargo_args.is_empty()
+            || !cargo_env.is_empty()
+            || !rustflags.is_empty()
+            || *replace_rustflags
+            || *offline
+            || *locked
+            || *frozen
+            || target_dir.is_some()
+            || toolchain.is_some()
+            || !cargo_config.is_empty()
+            || !unstable_flags.is_empty()
+            || *allow_unstable_on_stable
+            || *ignore_rust_version
+            || *no_required_features
+            || cargo_command.is_some()
+            || build_subcommand.is_some()
+            || *expect_fresh
+            || *ephemeral_target_dir
+            || *registry_cache
+            || *force_rebuild
+            || !matrix.is_empty()
+            || *fail_on_warnings
+            || *collect_messages
+            || *use_cross
+            || sanitizer.is_some()
+            || bench_target_dir.is_some()
+            || *include_libraries
+            || *collect_build_scripts
+            || *dependency_artifacts
+    }
+
+    /// Picks the executables of `package` from the 'CARGO_BIN_EXE_<name>' variables in
+    /// `vars`. `None` when a requested executable is missing or a binary of the package
+    /// needs features beyond the default ones, cargo sets the variables for those even when
+    /// they were not build.
+    fn bin_exe_executables<I>(
+        &self,
+        package: &Package,
+        vars: I,
+    ) -> Option<BTreeMap<String, Utf8PathBuf>>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut default_features = HashSet::new();
+        let mut pending = vec!["default"];
+        while let Some(feature) = pending.pop() {
+            if default_features.insert(feature) {
+                pending.extend(
+                    package
+                        .features
+                        .get(feature)
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str),
+                );
+            }
+        }
+        let feature_gated = package.targets.iter().any(|target| {
+            target
+                .required_features
+                .iter()
+                .any(|feature| !default_features.contains(feature.as_str()))
+        });
+        if feature_gated {
+            return None;
+        }
+
+        let executables: BTreeMap<String, Utf8PathBuf> = vars
+            .into_iter()
+            .filter_map(|(key, path)| {
+                let name = key.strip_prefix("CARGO_BIN_EXE_")?;
+                let path = Utf8PathBuf::from(path);
+                path.is_file().then(|| (String::from(name), path))
+            })
+            .filter(|(name, _)| {
+                self.specific_executables.is_empty() || self.specific_executables.contains(name)
+            })
+            .filter(|(name, _)| self.registers(name))
+            .collect();
+
+        let complete = self
+            .specific_executables
+            .iter()
+            .all(|name| executables.contains_key(name));
+        (complete && !executables.is_empty()).then_some(executables)
+    }
+
+    /// Stores the registered executables in the target directory and reuses them without
+    /// running 'cargo build' as long as nothing in the workspace, the configuration, the
+    /// toolchain or the build environment changed. Not used with `collect_messages()` and
+    /// `collect_build_scripts()`, which need the output of cargo.
+    pub fn registry_cache(mut self, cache: bool) -> Self {
+        self.registry_cache = cache;
+        self
+    }
+
+    /// Runs cargo even when the `registry_cache()` is up to date
+    pub fn force_rebuild(mut self) -> Self {
+        self.force_rebuild = true;
+        self
+    }
+
+    /// Returns the profile the build uses
+    fn resolved_profile(&self) -> String {
+        match (&self.profile, self.release) {
+            (Some(profile), _) => profile.clone(),
+            (None, Some(true)) => String::from("release"),
+            (None, Some(false)) => String::from("dev"),
+            (None, None) if running_bench() => String::from("bench"),
+            (None, None) => active_profile(),
+        }
+    }
+
+    /// Sets up building from a 'benches/' harness like criterion: the 'bench' profile and a
+    /// target directory of its own next to the one of 'cargo bench', so that the builds do
+    /// not invalidate each other. This is the default when the running executable was
+    /// started with '--bench', `profile()` and `target_dir()` take precedence.
+    pub fn for_benches(mut self) -> Self {
+        self.profile = Some(String::from("bench"));
+        self.bench_target_dir = Some(true);
+        self
+    }
+
+    /// The target directory for builds from benchmarks, 'bintest-bench' in the target
+    /// directory of the running benchmark
+    fn resolved_bench_target_dir(&self) -> Option<Utf8PathBuf> {
+        if self.target_dir.is_some()
+            || self.ephemeral_target_dir
+            || !self.bench_target_dir.unwrap_or_else(running_bench)
+        {
+            return None;
+        }
+        // benchmarks run from '<target-dir>/[<triple>/]<profile-dir>/deps/'
+        let exe = Utf8PathBuf::from_path_buf(std::env::current_exe().ok()?).ok()?;
+        Some(exe.parent()?.parent()?.parent()?.join("bintest-bench"))
+    }
+
+    /// Locates the `registry_cache()` in the target directory, the fingerprint covers the
+    /// workspace 'cargo metadata' reports
+    fn disk_cache(
+        &self,
+        metadata: &OnceCell<Metadata>,
+        profile: &str,
+    ) -> Result<Option<cache::RegistryCache>, Error> {
+        // the cached registry keeps neither compiler messages nor build script outputs
+        if !self.registry_cache
+            || self.ephemeral_target_dir
+            || self.collect_build_scripts
+            || self.collect_messages
+        {
+            return Ok(None);
+        }
+
+        let target_dir = match self.target_directory(metadata)? {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let workspace_root = &self.metadata(metadata)?.workspace_root;
+
+        let mut config = self.clone();
+        config.force_rebuild = false;
+        Ok(Some(cache::RegistryCache::new(
+            workspace_root,
+            &target_dir,
+            &format!("{:?} {}", config, profile),
+            &self.rustc_version_info().unwrap_or_default(),
+        )))
+    }
+
+    /// Limits the number of parallel jobs of the build ('-j n')
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Builds with the given rustup toolchain ('cargo +toolchain build'). When 'CARGO' is the
+    /// cargo of a toolchain, as under 'cargo test', the rustup proxy from the cargo home is
+    /// run instead, or 'rustup run toolchain cargo' when there is none. Only with a
+    /// `cargo_command()` that is not the proxy 'RUSTUP_TOOLCHAIN' is set for the build.
+    pub fn toolchain<S: Into<String>>(mut self, toolchain: S) -> Self {
+        self.toolchain = Some(toolchain.into());
+        self
+    }
+
+    /// Overrides a cargo configuration value ('--config key=value'), like
+    /// `cargo_config("profile.release.debug=true")`. Can be called multiple times, the
+    /// values are passed in order.
+    pub fn cargo_config<S: Into<String>>(mut self, kv: S) -> Self {
+        self.cargo_config.push(kv.into());
+        self
+    }
+
+    /// Passes an unstable flag to cargo ('-Z flag'), these are placed before the 'build'
+    /// subcommand. Requires a nightly `toolchain()` unless `allow_unstable_on_stable()` is set.
+    pub fn unstable_flag<S: Into<String>>(mut self, flag: S) -> Self {
+        self.unstable_flags.push(flag.into());
+        self
+    }
+
+    /// Allows `unstable_flag()` without an explicit nightly toolchain, for example when the
+    /// default toolchain is nightly or 'RUSTC_BOOTSTRAP' is used.
+    pub fn allow_unstable_on_stable(mut self) -> Self {
+        self.allow_unstable_on_stable = true;
+        self
+    }
+
+    /// Continues building other targets when one fails ('--keep-going'). Everything that was
+    /// build gets registered, failures are reported by `BinTest::failed_targets()` instead of
+    /// panicking.
+    pub fn keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Keeps all compiler messages of the build for `BinTest::messages()`, off by default
+    /// because they can take a lot of memory in big workspaces
+    pub fn collect_messages(mut self, collect: bool) -> Self {
+        self.collect_messages = collect;
+        self
+    }
+
+    /// Keeps what the build scripts of the workspace members reported, their 'OUT_DIR', cfgs
+    /// and environment, see `BinTest::build_script()`. The `registry_cache()` does not hold
+    /// them, it is not used then.
+    pub fn collect_build_scripts(mut self, collect: bool) -> Self {
+        self.collect_build_scripts = collect;
+        self
+    }
+
+    /// Fails the build when the compiler emitted warnings for workspace members, warnings
+    /// of dependencies are ignored
+    pub fn fail_on_warnings(mut self, fail: bool) -> Self {
+        self.fail_on_warnings = fail;
+        self
+    }
+
+    /// Registers only executables whose name matches one of the given glob patterns, '*'
+    /// matches any number of characters and '?' a single one. Qualified names like
+    /// 'example/name' are matched by the part after the '/'. Can be called multiple times.
+    pub fn register_matching<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.register_patterns.push(pattern.into());
+        self
+    }
+
+    /// Matches the patterns of `register_matching()` case-insensitively
+    pub fn match_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.match_ignore_case = ignore_case;
+        self
+    }
+
+    /// Never registers the executable with the given name, also when it is qualified like
+    /// 'package/name' or 'example/name'. Can be called multiple times.
+    pub fn exclude_executable<S: Into<String>>(mut self, name: S) -> Self {
+        self.excluded_executables.push(name.into());
+        self
+    }
+
+    /// Constructs the 'cargo build' command for this configuration
+    fn cargo_build_command(&self, features: &[String]) -> Result<Command, Error> {
+        let cargo = match &self.cargo_command {
+            Some(program) => program.clone(),
+            None if self.use_cross => OsString::from("cross"),
+            None => env("CARGO").unwrap_or_else(|| OsString::from("cargo")),
+        };
+        let mut cargo_build = match &self.toolchain {
+            // the cargo of a toolchain, as in 'CARGO' under 'cargo test', can not switch
+            // toolchains, the rustup proxy can
+            Some(toolchain) if self.cargo_command.is_none() && !is_rustup_proxy(&cargo) => {
+                match rustup_proxy("cargo") {
+                    Some(proxy) => {
+                        let mut cargo_build = Command::new(proxy);
+                        cargo_build.arg(format!("+{}", toolchain));
+                        cargo_build
+                    }
+                    None => {
+                        let mut cargo_build = Command::new("rustup");
+                        cargo_build.args(["run", toolchain, "cargo"]);
+                        cargo_build
+                    }
+                }
+            }
+            Some(toolchain) if is_rustup_proxy(&cargo) => {
+                let mut cargo_build = Command::new(&cargo);
+                cargo_build.arg(format!("+{}", toolchain));
+                cargo_build
+            }
+            Some(toolchain) => {
+                let mut cargo_build = Command::new(&cargo);
+                cargo_build.env("RUSTUP_TOOLCHAIN", toolchain);
+                cargo_build
+            }
+            None => Command::new(&cargo),
+        };
+
+        if self.capture_stderr {
+            cargo_build.stderr(Stdio::piped());
+        }
+
+        #[cfg(unix)]
+        if self.timeout.is_some() {
+            // own process group, so that a timeout kills build scripts and rustc as well
+            std::os::unix::process::CommandExt::process_group(&mut cargo_build, 0);
+        }
+
+        if self.toolchain.is_some() {
+            // captured to diagnose missing toolchains
+            cargo_build.stderr(Stdio::piped());
+        }
+
+        for flag in &self.unstable_flags {
+            cargo_build.args(["-Z", flag]);
+        }
+
+        cargo_build
+            .arg(
+                self.build_subcommand
+                    .as_deref()
+                    .unwrap_or_else(|| OsStr::new("build")),
+            )
+            .args([
+                "--message-format",
+                if self.color == Some(ColorChoice::Always) {
+                    "json,json-diagnostic-rendered-ansi"
+                } else {
+                    "json"
+                },
+            ])
+            .stdout(Stdio::piped());
+
+        let profile = self.resolved_profile();
+
+        match profile.as_str() {
+            "dev" => {}
+            "release" => {
+                cargo_build.arg("--release");
+            }
+            profile => {
+                cargo_build.args(["--profile", profile]);
+            }
+        }
+
+        if let Some(manifest_path) = self.resolved_manifest_path()? {
+            cargo_build.args(["--manifest-path", manifest_path.as_str()]);
+        }
+
+        if let Some(target) = &self.target {
+            cargo_build.args(["--target", target]);
+        }
+
+        if matches!(self.sanitizer, Some(Sanitizer::Memory | Sanitizer::Thread)) {
+            cargo_build.arg("-Zbuild-std");
+        }
+
+        if let Some(target_dir) = &self.target_dir {
+            cargo_build.args(["--target-dir", target_dir.as_str()]);
+        }
+
+        for kv in &self.cargo_config {
+            cargo_build.args(["--config", kv]);
+        }
+
+        if let Some(jobs) = self.jobs {
+            cargo_build.args(["-j", &jobs.to_string()]);
+        }
+
+        if self.build_workspace {
+            cargo_build.arg("--workspace");
+        }
+
+        for executable in &self.specific_executables {
+            cargo_build.args(["--bin", executable]);
+        }
+
+        if self.all_bins {
+            cargo_build.arg("--bins");
+        }
+
+        for example in &self.examples {
+            cargo_build.args(["--example", example]);
+        }
+
+        if self.all_examples {
+            cargo_build.arg("--examples");
+        }
+
+        if self.build_tests {
+            cargo_build.arg("--tests");
+        }
+
+        if self.build_benches {
+            cargo_build.arg("--benches");
+        }
+
+        for package in &self.packages {
+            cargo_build.args(["-p", package]);
+        }
+
+        for package in &self.excludes {
+            cargo_build.args(["--exclude", package]);
+        }
+
+        if self.offline {
+            cargo_build.arg("--offline");
+            // captured to diagnose missing dependencies
+            cargo_build.stderr(Stdio::piped());
+        }
+
+        if self.keep_going {
+            cargo_build.arg("--keep-going");
+        }
+
+        if self.ignore_rust_version {
+            cargo_build.arg("--ignore-rust-version");
+        }
+
+        if self.locked {
+            cargo_build.arg("--locked");
+        }
+
+        if self.frozen {
+            cargo_build.arg("--frozen");
+        }
+
+        if let Some(color) = self.color {
+            cargo_build.args([
+                "--color",
+                match color {
+                    ColorChoice::Auto => "auto",
+                    ColorChoice::Always => "always",
+                    ColorChoice::Never => "never",
+                },
+            ]);
+        }
+
+        match self.verbosity {
+            Verbosity::Silent => {
+                cargo_build.arg("--quiet");
+            }
+            Verbosity::Normal => {}
+            Verbosity::Verbose => {
+                cargo_build.arg("-v");
+            }
+            Verbosity::VeryVerbose => {
+                cargo_build.arg("-vv");
+            }
+        }
+
+        if !features.is_empty() {
+            cargo_build.args(["--features", &features.join(",")]);
+        }
+
+        if self.all_features {
+            cargo_build.arg("--all-features");
+        }
+
+        if self.no_default_features {
+            cargo_build.arg("--no-default-features");
+        }
+
+        cargo_build.args(self.passthrough_args());
+
+        if let Some(rustflags) = self.encoded_rustflags() {
+            cargo_build.env("CARGO_ENCODED_RUSTFLAGS", rustflags);
+        }
+
+        if let (Some(Sanitizer::Undefined), Some(target)) = (self.sanitizer, &self.target) {
+            for variable in ["CFLAGS", "CXXFLAGS"] {
+                let (key, flags) = cc_target_flags(variable, target);
+                let mut flags = flags.unwrap_or_default();
+                if !flags.is_empty() {
+                    flags.push(" ");
+                }
+                flags.push("-fsanitize=undefined");
+                cargo_build.env(key, flags);
+            }
+        }
+
+        if let Some(profile_file) = self.coverage_profile_file() {
+            cargo_build
+                .env("CARGO_INCREMENTAL", "0")
+                .env("LLVM_PROFILE_FILE", profile_file);
+        }
+
+        for (key, value) in &self.cargo_env {
+            match value {
+                Some(value) => cargo_build.env(key, value),
+                None => cargo_build.env_remove(key),
+            };
+        }
+
+        Ok(cargo_build)
+    }
+
+    /// Returns whether the name, or its part after a '/', was given to `exclude_executable()`
+    fn excludes_executable(&self, name: &str) -> bool {
+        let bare = name.rsplit(['/', ':']).next().unwrap_or(name);
+        self.excluded_executables
+            .iter()
+            .any(|excluded| excluded == name || excluded == bare)
+    }
+
+    /// Returns whether an executable with the given name is registered, see
+    /// `register_matching()` and `exclude_executable()`
+    fn registers(&self, name: &str) -> bool {
+        let bare = name.rsplit('/').next().unwrap_or(name);
+        !self.excludes_executable(name)
+            && (self.register_patterns.is_empty()
+                || self
+                    .register_patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern, bare, self.match_ignore_case)))
+    }
+
+    /// Makes `command()` remove the variables cargo sets for building and testing from the
+    /// environment of the executables, see `BinTest::command_sanitized()`
+    pub fn sanitize_env(mut self, sanitize: bool) -> Self {
+        self.sanitize_env = sanitize;
+        self
+    }
+
+    /// Looks up executables ignoring a trailing '.exe' or '.bat' and the case of the name,
+    /// `command("MyTool.exe")` finds 'mytool' then. Names are still registered as cargo
+    /// reports them. Enabled by default on Windows only.
+    pub fn lenient_lookup(mut self, lenient: bool) -> Self {
+        self.lenient_lookup = Some(lenient);
+        self
+    }
+
+    fn resolved_lenient_lookup(&self) -> bool {
+        self.lenient_lookup.unwrap_or(cfg!(windows))
+    }
+
+    /// Checks that the executable still exists every time a command is constructed, a
+    /// 'cargo clean' from another process gives then a clear error instead of 'NotFound'
+    /// when spawning it
+    pub fn verify_on_command(mut self, verify: bool) -> Self {
+        self.verify_on_command = verify;
+        self
+    }
+
+    /// Fails when anything had to be compiled, for CI stages where the executables are
+    /// supposed to be build already
+    pub fn expect_fresh(mut self, fresh: bool) -> Self {
+        self.expect_fresh = fresh;
+        self
+    }
+
+    /// Serializes bintest builds across processes with a lock file in the target directory,
+    /// for test runners like 'cargo nextest' which run every test in its own process. Fails
+    /// when the lock could not be taken within `timeout`.
+    pub fn cross_process_lock(mut self, timeout: Duration) -> Self {
+        self.cross_process_lock = Some(timeout);
+        self
+    }
+
+    /// Accepts a build that produced no executables at all, by default this is an error
+    pub fn allow_empty(mut self, allow: bool) -> Self {
+        self.allow_empty = allow;
+        self
+    }
+
+    /// Also registers the 'cdylib', 'dylib' and 'staticlib' targets of the selected packages,
+    /// see `BinTest::list_libraries()`. They are never handed out as commands.
+    pub fn include_libraries(mut self, include: bool) -> Self {
+        self.include_libraries = include;
+        self
+    }
+
+    /// Registers the executables of packages outside of the workspace, built as artifact
+    /// dependencies with 'bindeps' (`unstable_flag("bindeps")` until it is stable). They are
+    /// registered by target name, as 'package/name' when a workspace executable has the same
+    /// name, and are looked up as 'package::name' either way.
+    pub fn include_dependency_artifacts(mut self, include: bool) -> Self {
+        self.dependency_artifacts = include;
+        self
+    }
+
+    /// Kills cargo, together with everything it started, when the build takes longer than
+    /// `timeout` and fails with a timeout error
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Registers executables with the same name from different packages as 'package/name'
+    /// instead of failing. The bare name is then ambiguous and can not be looked up.
+    pub fn qualify_duplicates(mut self, qualify: bool) -> Self {
+        self.qualify_duplicates = qualify;
+        self
+    }
+
+    /// Skips the check of the packages 'rust-version' against the active rustc and passes
+    /// '--ignore-rust-version' to cargo
+    pub fn ignore_rust_version(mut self, ignore: bool) -> Self {
+        self.ignore_rust_version = ignore;
+        self
+    }
+
+    /// Fails when a package that is going to be build requires a newer rustc than the
+    /// active one, instead of letting the build fail with confusing errors
+    fn check_rust_version(&self, metadata: &OnceCell<Metadata>) -> Result<(), Error> {
+        let metadata = self.metadata(metadata)?;
+        let packages: Vec<&Package> = if !self.packages.is_empty() {
+            metadata
+                .workspace_packages()
+                .into_iter()
+                .filter(|package| {
+                    self.packages
+                        .iter()
+                        .any(|spec| spec.split(['@', ':']).next() == Some(package.name.as_str()))
+                })
+                .collect()
+        } else if self.build_workspace {
+            metadata
+                .workspace_packages()
+                .into_iter()
+                .filter(|package| !self.excludes.contains(&package.name))
+                .collect()
+        } else {
+            match metadata.root_package() {
+                Some(package) => vec![package],
+                None => metadata.workspace_packages(),
+            }
+        };
+
+        if packages
+            .iter()
+            .all(|package| package.rust_version.is_none())
+        {
+            return Ok(());
+        }
+
+        let rustc_version = self.rustc_version_info().and_then(|info| {
+            info.lines()
+                .find_map(|line| line.strip_prefix("release: "))
+                .and_then(|release| Version::parse(release.trim()).ok())
+        });
+        let Some(mut rustc_version) = rustc_version else {
+            // let cargo report whatever is wrong with rustc
+            return Ok(());
+        };
+        // nightly and beta versions satisfy the requirement of their release
+        rustc_version.pre = Prerelease::EMPTY;
+
+        for package in packages {
+            // a 'VersionReq' in cargo_metadata 0.15, a 'Version' in 0.16, both print as a
+            // valid requirement
+            let rust_version = package.rust_version.as_ref().map(ToString::to_string);
+            if let Some(rust_version) = rust_version {
+                let Ok(requirement) = VersionReq::parse(&rust_version) else {
+                    continue;
+                };
+                if !requirement.matches(&rustc_version) {
+                    return Err(Error::RustVersion {
+                        package: package.name.clone(),
+                        required: String::from(rust_version.trim_start_matches('^')),
+                        found: rustc_version.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Disables automatically enabling the 'required-features' of the executables requested
+    /// with `build_executable()`. Missing features are then reported instead.
+    pub fn no_required_features(mut self, manual: bool) -> Self {
+        self.no_required_features = manual;
+        self
+    }
+
+    /// Returns the features to build with, including the 'required-features' of the requested
+    /// executables. Features of packages other than the root package are qualified as
+    /// 'package/feature'.
+    fn features_with_required(&self, metadata: &OnceCell<Metadata>) -> Result<Vec<String>, Error> {
+        let mut features = self.features.clone();
+        if self.specific_executables.is_empty() || self.all_features {
+            return Ok(features);
+        }
+
+        let metadata = self.metadata(metadata)?;
+        let root = metadata.root_package().map(|package| &package.id);
+        for package in metadata.workspace_packages() {
+            for target in package.targets.iter().filter(|target| {
+                target.is_bin() && self.specific_executables.contains(&target.name)
+            }) {
+                let missing: Vec<String> = target
+                    .required_features
+                    .iter()
+                    .map(|feature| {
+                        if Some(&package.id) == root {
+                            feature.clone()
+                        } else {
+                            format!("{}/{}", package.name, feature)
+                        }
+                    })
+                    .filter(|feature| !features.contains(feature))
+                    .collect();
+
+                if !missing.is_empty() && self.no_required_features {
+                    return Err(Error::MissingFeatures {
+                        executable: target.name.clone(),
+                        features: missing,
+                    });
+                }
+                features.extend(missing);
+            }
+        }
+        Ok(features)
+    }
+
+    /// Returns the absolute target directory of the build, `None` when a relative
+    /// `target_dir()` can not be resolved
+    fn target_directory(
+        &self,
+        metadata: &OnceCell<Metadata>,
+    ) -> Result<Option<Utf8PathBuf>, Error> {
+        Ok(match &self.target_dir {
+            Some(dir) if dir.is_relative() => std::env::current_dir()
+                .ok()
+                .and_then(|cwd| Utf8PathBuf::from_path_buf(cwd).ok())
+                .map(|cwd| cwd.join(dir)),
+            Some(dir) => Some(dir.clone()),
+            None => Some(self.metadata(metadata)?.target_directory.clone()),
+        })
+    }
+
+    /// Takes the lock file in the target directory which serializes bintest builds of all
+    /// processes, see `cross_process_lock()`
+    fn lock_target_dir(
+        &self,
+        metadata: &OnceCell<Metadata>,
+    ) -> Result<Option<std::fs::File>, Error> {
+        let timeout = match self.cross_process_lock {
+            Some(timeout) => timeout,
+            None => return Ok(None),
+        };
+        let target_dir = match self.target_directory(metadata)? {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+
+        let lockfile = target_dir.join("bintest.lock");
+        std::fs::create_dir_all(&target_dir).map_err(Error::Lock)?;
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lockfile)
+            .map_err(Error::Lock)?;
+
+        let start = Instant::now();
+        let mut logged = false;
+        loop {
+            if file.try_lock_exclusive().map_err(Error::Lock)? {
+                break;
+            }
+
+            // the holder writes its pid into the lock file
+            let holder = std::fs::read_to_string(&lockfile)
+                .ok()
+                .and_then(|pid| pid.trim().parse().ok());
+            if start.elapsed() >= timeout {
+                return Err(Error::LockTimeout { lockfile, holder });
+            }
+            if !logged && self.verbosity != Verbosity::Silent {
+                match holder {
+                    Some(pid) => eprintln!(
+                        "bintest: waiting for <<{}>> held by process {}",
+                        lockfile, pid
+                    ),
+                    None => eprintln!("bintest: waiting for <<{}>>", lockfile),
+                }
+                logged = true;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        file.set_len(0).map_err(Error::Lock)?;
+        write!(file, "{}", std::process::id()).map_err(Error::Lock)?;
+        Ok(Some(file))
+    }
+
+    /// Figures out the target triple the build used. Unless given explicitly this comes from
+    /// 'CARGO_BUILD_TARGET' or is inferred from the location of the artifacts, which covers
+    /// 'build.target' in the cargo configuration.
+    fn effective_target<'a>(
+        &self,
+        metadata: &OnceCell<Metadata>,
+        mut executables: impl Iterator<Item = &'a Utf8PathBuf>,
+        profile: &str,
+    ) -> Result<Option<String>, Error> {
+        if let Some(target) = &self.target {
+            return Ok(Some(target.clone()));
+        }
+
+        let from_env = match self
+            .cargo_env
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "CARGO_BUILD_TARGET")
+        {
+            Some((_, value)) => value.clone(),
+            None => env("CARGO_BUILD_TARGET"),
+        };
+        if let Some(target) = from_env {
+            return Ok(Some(target.to_string_lossy().into_owned()));
+        }
+
+        // artifacts are placed in '<target-dir>/[<triple>/]<profile-dir>/'
+        let target_dir = match self.target_directory(metadata)? {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let profile_dir = profile_dir(profile);
+        Ok(executables
+            .next()
+            .and_then(|executable| executable.strip_prefix(&target_dir).ok())
+            .and_then(|relative| relative.components().next())
+            .map(|component| String::from(component.as_str()))
+            .filter(|first| first != profile_dir))
+    }
+
+    /// Uses the given program instead of cargo, for example 'cargo-zigbuild' or a wrapper
+    /// script. Defaults to 'CARGO' from the environment or 'cargo'.
+    pub fn cargo_command<S: Into<OsString>>(mut self, program: S) -> Self {
+        self.cargo_command = Some(program.into());
+        self
+    }
+
+    /// Builds with the 'cross' tool instead of cargo, together with `target()`. Paths cross
+    /// reports from inside its container are mapped back to the host. cross runs the
+    /// executables with qemu inside the container only, the commands use `runner()` or the
+    /// runner from the cargo configuration, e.g. `runner("qemu-aarch64", ["-L",
+    /// "/usr/aarch64-linux-gnu"])`. `cargo_command()` takes precedence.
+    pub fn use_cross(mut self, cross: bool) -> Self {
+        self.use_cross = cross;
+        self
+    }
+
+    /// Returns the directories cross mounts into its container with their host location,
+    /// empty unless building with `use_cross()`
+    fn container_mounts(
+        &self,
+        metadata: &OnceCell<Metadata>,
+    ) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>, Error> {
+        if !self.use_cross || self.cargo_command.is_some() {
+            return Ok(Vec::new());
+        }
+        let mut mounts = Vec::new();
+        if let Some(target_dir) = self.target_directory(metadata)? {
+            mounts.push((Utf8PathBuf::from("/target"), target_dir));
+        }
+        let workspace_root = self.metadata(metadata)?.workspace_root.clone();
+        mounts.push((Utf8PathBuf::from("/project"), workspace_root));
+        Ok(mounts)
+    }
+
+    /// Uses the given subcommand instead of 'build', for example 'zigbuild'. The output must
+    /// still be cargo's JSON messages, '--message-format json' is always appended.
+    pub fn build_subcommand<S: Into<OsString>>(mut self, sub: S) -> Self {
+        self.build_subcommand = Some(sub.into());
+        self
+    }
+
+    /// Skips checking the targets requested by `build_executable()` and `build_example()`
+    /// against 'cargo metadata' before building, for setups where the metadata does not
+    /// reflect what gets build.
+    pub fn skip_target_check(mut self, skip: bool) -> Self {
+        self.skip_target_check = skip;
+        self
+    }
+
+    /// Returns the name of a package, from the metadata when possible
+    fn package_name(&self, metadata: &OnceCell<Metadata>, package_id: &PackageId) -> String {
+        self.metadata(metadata)
+            .ok()
+            .and_then(|metadata| {
+                metadata
+                    .packages
+                    .iter()
+                    .find(|package| package.id == *package_id)
+            })
+            .map(|package| package.name.clone())
+            .unwrap_or_else(|| package_id.repr.clone())
+    }
+
+    /// Returns true for packages outside of the workspace, false when the metadata is missing
+    fn is_dependency(&self, metadata: &OnceCell<Metadata>, package_id: &PackageId) -> bool {
+        self.metadata(metadata)
+            .is_ok_and(|metadata| !metadata.workspace_members.contains(package_id))
+    }
+
+    /// Finds the executable in the 'deps' directory that was compiled from `src_path`, using
+    /// the dep-info files cargo writes next to it
+    fn deps_executable(
+        &self,
+        metadata: &OnceCell<Metadata>,
+        executable: &Utf8Path,
+        src_path: &Utf8Path,
+    ) -> Option<Utf8PathBuf> {
+        let workspace_root = &self.metadata(metadata).ok()?.workspace_root;
+        let deps = executable.parent()?.join("deps");
+        let prefix = format!("{}-", executable.file_stem()?);
+
+        for entry in deps.read_dir_utf8().ok()?.filter_map(Result::ok) {
+            let dep_info = entry.path();
+            if !entry.file_name().starts_with(&prefix) || dep_info.extension() != Some("d") {
+                continue;
+            }
+            let contents = std::fs::read_to_string(dep_info).ok()?;
+            let compiled_from = contents
+                .split_whitespace()
+                .any(|source| workspace_root.join(source) == src_path);
+            if compiled_from {
+                let mut candidate = dep_info.with_extension("");
+                if let Some(extension) = executable.extension() {
+                    candidate.set_extension(extension);
+                }
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+        None
+    }
+
+    /// Fails when a requested executable or example does not exist in the workspace
+    fn check_requested_targets(&self, metadata: &OnceCell<Metadata>) -> Result<(), Error> {
+        if self.skip_target_check
+            || (self.specific_executables.is_empty() && self.examples.is_empty())
+        {
+            return Ok(());
+        }
+
+        let metadata = self.metadata(metadata)?;
+        for (kind, requested) in [
+            ("bin", &self.specific_executables),
+            ("example", &self.examples),
+        ] {
+            let available: Vec<String> = metadata
+                .workspace_packages()
+                .into_iter()
+                .flat_map(|package| package.targets.iter())
+                .filter(|target| target.kind.iter().any(|k| k == kind))
+                .map(|target| target.name.clone())
+                .collect();
+
+            if let Some(name) = requested.iter().find(|name| !available.contains(name)) {
+                return Err(Error::UnknownTarget {
+                    kind,
+                    name: name.clone(),
+                    suggestion: closest_match(name, &available).cloned(),
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the lockfile path and its contents when locked or frozen mode is requested
+    fn lockfile_snapshot(
+        &self,
+        metadata: &OnceCell<Metadata>,
+    ) -> Result<Option<LockfileSnapshot>, Error> {
+        if !self.locked && !self.frozen {
+            return Ok(None);
+        }
+
+        let lockfile = self.metadata(metadata)?.workspace_root.join("Cargo.lock");
+        let contents = std::fs::read(&lockfile).ok();
+        Ok(Some((lockfile, contents)))
+    }
+
+    /// Returns the user supplied cargo arguments without any '--message-format'
+    fn passthrough_args(&self) -> Vec<&OsString> {
+        let mut args = Vec::new();
+        let mut skip_value = false;
+        for arg in &self.cargo_args {
+            if skip_value {
+                skip_value = false;
+            } else if arg == "--message-format" {
+                skip_value = true;
+            } else if !arg.to_string_lossy().starts_with("--message-format=") {
+                args.push(arg);
+            }
+        }
+        args
+    }
+
+    /// Runs 'cargo metadata' for the workspace that is going to be build, only once per build
+    fn metadata<'a>(&self, cache: &'a OnceCell<Metadata>) -> Result<&'a Metadata, Error> {
+        if let Some(metadata) = cache.get() {
+            return Ok(metadata);
+        }
+
+        let mut command = MetadataCommand::new();
+        // the packages of artifact dependencies give the names of their executables
+        if !self.dependency_artifacts {
+            command.no_deps();
+        }
+        if let Some(manifest_path) = self.resolved_manifest_path()? {
+            command.manifest_path(manifest_path);
+        }
+        // manifests can depend on unstable features like 'bindeps' as well
+        if let Some(toolchain) = &self.toolchain {
+            if let Some(proxy) = rustup_proxy("cargo") {
+                command.cargo_path(proxy);
+            }
+            command.env("RUSTUP_TOOLCHAIN", toolchain);
+        }
+        command.other_options(
+            self.unstable_flags
+                .iter()
+                .map(|flag| format!("-Z{}", flag))
+                .collect::<Vec<_>>(),
+        );
+        let metadata = command.exec().map_err(|err| match &self.toolchain {
+            Some(toolchain) if !is_toolchain_installed(toolchain) => {
+                Error::ToolchainNotInstalled(toolchain.clone())
+            }
+            _ => Error::Metadata(err),
+        })?;
+        Ok(cache.get_or_init(|| metadata))
+    }
+
+    /// Returns the ids of the selected packages, `None` when no packages were selected
+    fn selected_package_ids(
+        &self,
+        metadata: &OnceCell<Metadata>,
+    ) -> Result<Option<HashSet<PackageId>>, Error> {
+        if self.packages.is_empty() {
+            return Ok(None);
+        }
+
+        let metadata = self.metadata(metadata)?;
+        self.packages
+            .iter()
+            .map(|spec| {
+                // package specs may carry a version as in 'name@1.0' or 'name:1.0'
+                let name = spec.split(['@', ':']).next().unwrap_or(spec);
+                metadata
+                    .workspace_packages()
+                    .into_iter()
+                    .find(|package| package.name == name)
+                    .map(|package| package.id.clone())
+                    .ok_or_else(|| Error::UnknownPackage(spec.clone()))
+            })
+            .collect::<Result<HashSet<_>, _>>()
+            .map(Some)
+    }
+
+    /// Describes the filters that limit what gets build
+    fn active_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+        filters.extend(
+            self.packages
+                .iter()
+                .map(|package| format!("-p {}", package)),
+        );
+        filters.extend(
+            self.excludes
+                .iter()
+                .map(|package| format!("--exclude {}", package)),
+        );
+        filters.extend(
+            self.specific_executables
+                .iter()
+                .map(|name| format!("--bin {}", name)),
+        );
+        filters.extend(
+            self.examples
+                .iter()
+                .map(|name| format!("--example {}", name)),
+        );
+        if !self.features.is_empty() {
+            filters.push(format!("--features {}", self.features.join(",")));
+        }
+        if self.no_default_features {
+            filters.push(String::from("--no-default-features"));
+        }
+        if self.no_required_features {
+            filters.push(String::from("no_required_features(true)"));
+        }
+        filters.extend(
+            self.register_patterns
+                .iter()
+                .map(|pattern| format!("register_matching({:?})", pattern)),
+        );
+        filters.extend(
+            self.excluded_executables
+                .iter()
+                .map(|name| format!("exclude_executable({:?})", name)),
+        );
+        filters
+    }
+
+    /// Explains an empty build, with the workspace members that were build and where
+    /// executables could be found instead
+    fn no_executables(
+        &self,
+        metadata: &OnceCell<Metadata>,
+        artifacts: Vec<(PackageId, String)>,
+    ) -> Error {
+        let mut built = Vec::new();
+        let mut suggestions = Vec::new();
+
+        if let Ok(metadata) = self.metadata(metadata) {
+            built = artifacts
+                .into_iter()
+                .filter(|(package_id, _)| metadata.workspace_members.contains(package_id))
+                .map(|(_, target)| target)
+                .collect();
+
+            let built_package = |package: &Package| {
+                if self.packages.is_empty() {
+                    metadata.root_package().map(|root| &root.id) == Some(&package.id)
+                } else {
+                    self.packages
+                        .iter()
+                        .any(|spec| spec.split(['@', ':']).next() == Some(package.name.as_str()))
+                }
+            };
+            let has_target = |kind: &str, other_packages: bool| {
+                metadata
+                    .workspace_packages()
+                    .into_iter()
+                    .filter(|package| built_package(package) != other_packages)
+                    .flat_map(|package| package.targets.iter())
+                    .any(|target| target.kind.iter().any(|k| k == kind))
+            };
+            if !self.build_workspace
+                && self.specific_executables.is_empty()
+                && has_target("bin", true)
+            {
+                suggestions.push(String::from(
+                    "other workspace members have binaries, try build_workspace(true)",
+                ));
+            }
+            if !self.all_examples && self.examples.is_empty() && has_target("example", false) {
+                suggestions.push(String::from(
+                    "the workspace has examples, try build_examples(true)",
+                ));
+            }
+        }
+
+        Error::NoExecutables {
+            built,
+            filters: self.active_filters(),
+            suggestions,
+        }
+    }
+
+    /// Checks the configuration for options that contradict each other, this is done by
+    /// `build()` and `try_build()` as well. All conflicts found are reported at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut conflicts = Vec::new();
+
+        if self.all_features && !self.features.is_empty() {
+            conflicts.push(format!(
+                "all_features() can not be combined with an explicit feature list {:?}",
+                self.features
+            ));
+        }
+        if !self.specific_executables.is_empty() && self.build_workspace {
+            conflicts.push(format!(
+                "build_executable({:?}) selects single executables, \
+                 build_workspace(true) would be ignored",
+                self.specific_executables
+            ));
+        }
+        if !self.specific_executables.is_empty() && self.all_bins {
+            conflicts.push(format!(
+                "build_executable({:?}) can not be combined with all_bins(true)",
+                self.specific_executables
+            ));
+        }
+        if !self.examples.is_empty() && self.all_examples {
+            conflicts.push(format!(
+                "build_example({:?}) can not be combined with build_examples(true)",
+                self.examples
+            ));
+        }
+        if !self.build_workspace && !self.excludes.is_empty() {
+            conflicts.push(format!(
+                "exclude({:?}) is only valid together with build_workspace(true)",
+                self.excludes
+            ));
+        }
+        let excluded: Vec<&String> = self
+            .specific_executables
+            .iter()
+            .filter(|name| self.excluded_executables.contains(name))
+            .collect();
+        if !excluded.is_empty() {
+            conflicts.push(format!(
+                "build_executable({:?}) requests executables given to exclude_executable()",
+                excluded
+            ));
+        }
+        for pattern in &self.register_patterns {
+            if let Err(err) = check_pattern(pattern) {
+                conflicts.push(format!("register_matching(): {}", err));
+            }
+        }
+        if self.cargo_config.iter().any(|kv| kv.trim().is_empty()) {
+            conflicts.push(String::from("cargo_config() values must not be empty"));
+        }
+        if self.ephemeral_target_dir && self.target_dir.is_some() {
+            conflicts.push(String::from(
+                "ephemeral_target_dir() can not be combined with target_dir()",
+            ));
+        }
+        if !self.matrix.is_empty() && self.ephemeral_target_dir {
+            conflicts.push(String::from(
+                "matrix() uses its own target directories, ephemeral_target_dir() can not be \
+                 combined with it",
+            ));
+        }
+        for set in &self.matrix {
+            if set.label.is_empty() || set.label.contains(['/', '\\', '@']) {
+                conflicts.push(format!(
+                    "matrix() label {:?} must not be empty or contain '/', '\\' or '@'",
+                    set.label
+                ));
+            }
+        }
+        if self.jobs == Some(0) {
+            conflicts.push(String::from("jobs(0) would not build anything"));
+        }
+        if !self.unstable_flags.is_empty()
+            && !self.allow_unstable_on_stable
+            && !self
+                .toolchain
+                .as_ref()
+                .is_some_and(|toolchain| toolchain.starts_with("nightly"))
+        {
+            conflicts.push(format!(
+                "unstable_flag({:?}) requires a nightly toolchain(), \
+                 use allow_unstable_on_stable() to override this check",
+                self.unstable_flags
+            ));
+        }
+        if let Some(toolchain) = &self.toolchain {
+            if (self.frozen || self.offline) && !is_toolchain_installed(toolchain) {
+                conflicts.push(format!(
+                    "toolchain({:?}) is not installed and can not be fetched in {} mode",
+                    toolchain,
+                    if self.frozen { "frozen" } else { "offline" }
+                ));
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { conflicts })
+        }
+    }
+
+    /// Runs 'cargo build' with this configuration and registers the build executables.
+    /// Panics on any error.
+    pub fn build(self) -> BinTest {
+        self.try_build()
+            .unwrap_or_else(|err| panic!("bintest: {}", err))
+    }
+
+    /// Runs 'cargo build' with this configuration and registers the build executables
+    pub fn try_build(self) -> Result<BinTest, Error> {
+        BinTest::new_with_builder(self)
+    }
+
+    /// Returns the cargo command line `try_build()` would run, without running cargo. The
+    /// target directory of `ephemeral_target_dir()` is created only when building, and the
+    /// 'required-features' `build_executable()` enables come from 'cargo metadata', neither
+    /// are part of it.
+    pub fn dry_run(&self) -> Result<CargoInvocation, Error> {
+        self.validate().map_err(Error::InvalidConfig)?;
+        let mut builder = self.clone();
+        builder.prepare()?;
+        let command = builder.cargo_build_command(&builder.features)?;
+        Ok(CargoInvocation::from_command(&command))
+    }
+
+    /// Starts the build on a background thread right away, so that other test setup can
+    /// overlap with it. The result is available from the returned `BuildHandle`.
+    pub fn spawn_build(self) -> BuildHandle {
+        BuildHandle::spawn(self)
+    }
+
+    /// Builds only once per process for each configuration, all later calls with the same
+    /// configuration share the result. Panics on any error.
+    pub fn build_cached(self) -> Arc<BinTest> {
+        self.try_build_cached()
+            .unwrap_or_else(|err| panic!("bintest: {}", err))
+    }
+
+    /// Builds only once per process for each configuration, all later calls with the same
+    /// configuration share the result. Errors are not cached, the next call builds again.
+    pub fn try_build_cached(self) -> Result<Arc<BinTest>, Error> {
+        // the shared build lives until the process exits and would leak the directory
+        if self.ephemeral_target_dir {
+            return Err(Error::InvalidConfig(ConfigError {
+                conflicts: vec![String::from(
+                    "ephemeral_target_dir() can not be combined with build_cached() or \
+                     global(), the shared build is never dropped",
+                )],
+            }));
+        }
+        let slot = {
+            let mut cache = CACHED_BUILDS.lock().unwrap_or_else(PoisonError::into_inner);
+            Arc::clone(cache.entry(self.fingerprint()).or_default())
+        };
+
+        // callers with the same configuration wait for the first one, others proceed
+        let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(cached) = &*slot {
+            cached.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Arc::clone(cached));
+        }
+        let bintest = Arc::new(self.try_build()?);
+        *slot = Some(Arc::clone(&bintest));
+        Ok(bintest)
+    }
+
+    /// Identifies the configuration for `build_cached()`, the `Debug` output covers every
+    /// field so that different configurations never share a build
+    fn fingerprint(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// A build shared by all `build_cached()` callers with the same configuration
+type CachedBuild = Arc<Mutex<Option<Arc<BinTest>>>>;
+
+static CACHED_BUILDS: Mutex<BTreeMap<String, CachedBuild>> = Mutex::new(BTreeMap::new());
+
+// the configuration given to `init()`, taken by the first `global()` call
+static GLOBAL_BUILDER: Mutex<Option<BinTestBuilder>> = Mutex::new(None);
+static GLOBAL: OnceLock<Arc<BinTest>> = OnceLock::new();
+
+/// Configures the build of the process wide registry used by `command!()` and `global()`.
+/// Panics when the registry was already build or configured.
+#[track_caller]
+pub fn init(builder: BinTestBuilder) {
+    let mut global_builder = GLOBAL_BUILDER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if GLOBAL.get().is_some() {
+        panic!("bintest: init() was called after the global registry was already build");
+    }
+    if global_builder.is_some() {
+        panic!("bintest: init() was called twice");
+    }
+    *global_builder = Some(builder);
+}
+
+/// Returns the process wide registry, build on first use with the configuration given to
+/// `init()` or the default one. Panics on any error.
+pub fn global() -> &'static BinTest {
+    GLOBAL.get_or_init(|| {
+        GLOBAL_BUILDER
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .unwrap_or_default()
+            .build_cached()
+    })
+}
+
+/// Constructs a 'std::process::Command' for the executable with the given name from the
+/// `global()` registry. Only string literals are accepted.
+///
+/// ```no_run
+/// let output = bintest::command!("mybin").arg("--version").output().unwrap();
+/// ```
+#[macro_export]
+macro_rules! command {
+    ($name:literal) => {{
+        let name: &'static str = $name;
+        $crate::global().command(name)
+    }};
+}
+
+// Fallback when the profile can not be inferred from the location of the test executable
+#[cfg(not(debug_assertions))]
+const RELEASE_BUILD: bool = true;
+
+#[cfg(debug_assertions)]
+const RELEASE_BUILD: bool = false;
+
+/// Returns true when the running executable is a benchmark harness, 'cargo bench' passes
+/// '--bench' to it
+fn running_bench() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--bench")
+}
+
+/// The directory below 'target/[<triple>/]' cargo places the artifacts of a profile in. The
+/// builtin 'dev' and 'test' profiles share 'debug', 'release' and 'bench' share 'release',
+/// custom profiles get a directory of their own name.
+fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "release" | "bench" => "release",
+        custom => custom,
+    }
+}
+
+/// Figures out the profile the running test executable was build with. Cargo places test
+/// executables in the directory given by `profile_dir()`, below 'target/[<triple>/]'.
+fn active_profile() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            let deps = exe.parent()?;
+            if deps.file_name()? != "deps" {
+                return None;
+            }
+            Some(String::from(deps.parent()?.file_name()?.to_str()?))
+        })
+        .map(|dir| match dir.as_str() {
+            dir if dir == profile_dir("dev") => String::from("dev"),
+            _ => dir,
+        })
+        .unwrap_or_else(|| String::from(if RELEASE_BUILD { "release" } else { "dev" }))
+}
+
+/// Limits the number of concurrent bintest driven builds in this process
+struct BuildSemaphore {
+    running: Mutex<usize>,
+    released: Condvar,
+}
+
+static BUILD_SEMAPHORE: BuildSemaphore = BuildSemaphore {
+    running: Mutex::new(0),
+    released: Condvar::new(),
+};
+
+static MAX_CONCURRENT_BUILDS: AtomicUsize = AtomicUsize::new(1);
+
+/// Gives the build slot back when dropped
+struct BuildPermit;
+
+impl BuildSemaphore {
+    /// Blocks until a build slot is available, returns whether it had to wait for it
+    fn acquire(&'static self) -> (BuildPermit, bool) {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut waited = false;
+        while *running >= MAX_CONCURRENT_BUILDS.load(Ordering::Relaxed) {
+            waited = true;
+            running = self
+                .released
+                .wait(running)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *running += 1;
+        (BuildPermit, waited)
+    }
+}
+
+impl Drop for BuildPermit {
+    fn drop(&mut self) {
+        *BUILD_SEMAPHORE
+            .running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) -= 1;
+        BUILD_SEMAPHORE.released.notify_one();
+    }
+}
+
+/// Temporary target directory of `ephemeral_target_dir()`
+struct EphemeralDir {
+    path: Utf8PathBuf,
+    keep: bool,
+}
+
+impl EphemeralDir {
+    /// Creates a new, unique directory in the systems temporary directory
+    fn create() -> Result<EphemeralDir, Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let temp_dir = Utf8PathBuf::from_path_buf(std::env::temp_dir()).map_err(|dir| {
+            Error::TempDir(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} is not valid UTF-8", dir.display()),
+            ))
+        })?;
+        let path = temp_dir.join(format!(
+            "bintest-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        // a leftover of a previous process with the same pid
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).map_err(Error::TempDir)?;
+        Ok(EphemeralDir { path, keep: false })
+    }
+}
+
+impl Drop for EphemeralDir {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        // windows refuses to remove executables which are still mapped by exiting processes
+        for _ in 0..10 {
+            if std::fs::remove_dir_all(&self.path).is_ok() || !self.path.exists() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+/// Finds the candidate most similar to `name` for "did you mean" suggestions
+fn closest_match<'a>(name: &str, candidates: &'a [String]) -> Option<&'a String> {
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Runs the command and returns its stdout, panics with the command line, exit status and
+/// stderr when it could not be started or failed
+#[track_caller]
+fn run_successfully(command: &mut Command) -> String {
+    let output = command
+        .output()
+        .unwrap_or_else(|err| panic!("bintest: failed to run {:?}: {}", command, err));
+    if !output.status.success() {
+        panic!(
+            "bintest: {:?} failed with {}\n--- stderr ---\n{}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Returns the part of `path` below the profile directory `profile` with '/' as separator,
+/// starting with the profile directory when `profile_dir` is given. The '.exe' suffix and
+/// the hash of files in 'deps/' are removed.
+fn normalized_path(path: &Utf8Path, profile: &str, profile_dir: bool) -> String {
+    let components: Vec<&str> = path.components().map(|c| c.as_str()).collect();
+    let start = match components.iter().rposition(|c| *c == profile) {
+        Some(index) if profile_dir => index,
+        Some(index) => index + 1,
+        None => components.len().saturating_sub(1),
+    };
+    let mut normalized = components[start..].join("/");
+    if let Some(stem) = normalized.strip_suffix(".exe") {
+        normalized.truncate(stem.len());
+    }
+    if components.get(components.len().wrapping_sub(2)) == Some(&"deps") {
+        // 'name-<16 hex digits>'
+        if let Some((stem, hash)) = normalized.rsplit_once('-') {
+            if hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                normalized.truncate(stem.len());
+            }
+        }
+    }
+    normalized
+}
+
+/// Returns the 'BINTEST_BIN_<NAME>' variable of `BinTest::env_vars()` for an executable
+fn bin_env_name(name: &str) -> String {
+    let mangled: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("BINTEST_BIN_{}", mangled)
+}
+
+/// Removes the build variables but the ones in `keep` from the environment of `command`
+fn remove_build_variables(command: &mut Command, keep: &[&str]) {
+    for (key, _) in std::env::vars_os() {
+        if let Some(key) = key.to_str() {
+            if is_build_variable(key) && !keep.contains(&key) {
+                command.env_remove(key);
+            }
+        }
+    }
+}
+
+/// Checks that a pattern for `glob_match()` uses only the supported syntax
+fn check_pattern(pattern: &str) -> Result<(), PatternError> {
+    let reason = if pattern.is_empty() {
+        "the pattern is empty"
+    } else if pattern.contains(['[', ']', '{', '}']) {
+        "only '*' and '?' are supported"
+    } else {
+        return Ok(());
+    };
+    Err(PatternError {
+        pattern: String::from(pattern),
+        reason: String::from(reason),
+    })
+}
+
+/// Matches a name against a glob pattern where '*' matches any number of characters and '?'
+/// exactly one
+fn glob_match(pattern: &str, name: &str, ignore_case: bool) -> bool {
+    let fold = |text: &str| -> Vec<char> {
+        if ignore_case {
+            text.to_lowercase().chars().collect()
+        } else {
+            text.chars().collect()
+        }
+    };
+    let (pattern, name) = (fold(pattern), fold(name));
+
+    // on a mismatch the last '*' takes one more character
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, taken)) => {
+                    p = star + 1;
+                    n = taken + 1;
+                    backtrack = Some((star, taken + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Returns true for the variables cargo and rustup set when building and running tests.
+/// Configuration a user sets as well, like 'CARGO_HOME', 'RUSTUP_HOME', 'RUSTFLAGS' or
+/// 'CARGO_TERM_COLOR', is kept.
+fn is_build_variable(key: &str) -> bool {
+    const VARIABLES: &[&str] = &[
+        "CARGO",
+        "CARGO_CRATE_NAME",
+        "CARGO_PRIMARY_PACKAGE",
+        "CARGO_TARGET_TMPDIR",
+        "OUT_DIR",
+    ];
+    const PREFIXES: &[&str] = &["CARGO_MANIFEST_", "CARGO_PKG_", "CARGO_BIN_", "__CARGO"];
+    VARIABLES.contains(&key)
+        || PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+        || (key.starts_with("RUSTUP_") && key != "RUSTUP_HOME")
+}
+
+/// Returns true when 'cc', the linker rustc uses by default, is gcc
+fn linker_is_gcc() -> bool {
+    Command::new("cc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("Free Software"))
+}
+
+/// Returns the variable the 'cc' crate takes the flags for `target` from, like
+/// 'CFLAGS_x86_64_unknown_linux_gnu', with the flags already set for the target. cc prefers
+/// the target specific variables over the plain one.
+fn cc_target_flags(variable: &str, target: &str) -> (String, Option<OsString>) {
+    let key = format!("{}_{}", variable, target.replace('-', "_"));
+    let flags = env(format!("{}_{}", variable, target))
+        .or_else(|| env(&key))
+        .or_else(|| env(variable));
+    (key, flags)
+}
+
+/// Maps a path cross reported from inside its container to the host, paths that exist are
+/// left alone since newer versions of cross mount everything at the same location
+fn map_container_path(path: &mut Utf8PathBuf, mounts: &[(Utf8PathBuf, Utf8PathBuf)]) {
+    if mounts.is_empty() || path.exists() {
+        return;
+    }
+    for (inside, host) in mounts {
+        if let Ok(relative) = path.strip_prefix(inside) {
+            *path = host.join(relative);
+            return;
+        }
+    }
+}
+
+/// Returns the kind an artifact is registered as, examples, benches and tests are namespaced
+fn artifact_kind(artifact: &Artifact) -> &'static str {
+    if artifact.target.kind.iter().any(|kind| kind == "example") {
+        "example"
+    } else if artifact.target.kind.iter().any(|kind| kind == "bench") {
+        "bench"
+    } else if artifact.profile.test {
+        "test"
+    } else {
+        "bin"
+    }
+}
+
+/// Returns the library file of a 'cdylib', 'dylib' or 'staticlib' artifact. Shared objects
+/// come first, on Windows the import library of a '.dll' has the '.lib' extension too.
+fn library_file(artifact: &Artifact) -> Option<&Utf8PathBuf> {
+    let library = artifact
+        .target
+        .kind
+        .iter()
+        .any(|kind| matches!(kind.as_str(), "cdylib" | "dylib" | "staticlib"));
+    if !library || artifact.executable.is_some() {
+        return None;
+    }
+    ["so", "dylib", "dll", "a", "lib"]
+        .iter()
+        .find_map(|extension| {
+            artifact
+                .filenames
+                .iter()
+                .find(|file| file.extension() == Some(*extension))
+        })
+}
+
+/// Returns the separate debug info among the files of an artifact. Cargo lists '.pdb', '.dSYM'
+/// and '.dwp' outputs, unpacked '.dwo' files only when they are uplifted.
+fn debug_file(artifact: &Artifact) -> Option<&Utf8PathBuf> {
+    ["pdb", "dSYM", "dwp", "dwo"].iter().find_map(|extension| {
+        artifact
+            .filenames
+            .iter()
+            .find(|file| file.extension() == Some(*extension))
+    })
+}
+
+/// Runs a command to completion like `Command::output()`, but terminates it together with
+/// everything it started when it takes longer than `timeout`. The output produced until then
+/// is part of the error.
+fn output_with_timeout(
+    command: &mut Command,
+    timeout: Duration,
+) -> Result<std::process::Output, TimeoutError> {
+    use std::io::Read;
+
+    // in its own process group, so that its children can be terminated as well
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let start = Instant::now();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(TimeoutError::Spawn)?;
+
+    // both pipes are drained concurrently, a full pipe would block the child otherwise
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    };
+    let stdout = drain(
+        child
+            .stdout
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    );
+    let stderr = drain(
+        child
+            .stderr
+            .take()
+            .map(|pipe| Box::new(pipe) as Box<dyn Read + Send>),
+    );
+
+    let pid = child.id();
+    let (exited, exit) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = exited.send(child.wait());
+    });
+
+    let (status, expired) = match exit.recv_timeout(timeout) {
+        Ok(status) => (status, false),
+        Err(_) => {
+            terminate_process_tree(pid);
+            let status = match exit.recv_timeout(TERMINATE_GRACE) {
+                Ok(status) => status,
+                Err(_) => {
+                    kill_process_tree(pid);
+                    exit.recv().expect("waiting thread reports the exit")
+                }
+            };
+            // descendants ignoring the request would keep the pipes open
+            kill_process_tree(pid);
+            (status, true)
+        }
+    };
+    let output = std::process::Output {
+        status: status.map_err(TimeoutError::Spawn)?,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+
+    if expired {
+        Err(TimeoutError::Expired {
+            timeout,
+            elapsed: start.elapsed(),
+            output,
+        })
+    } else {
+        Ok(output)
+    }
+}
+
+/// Same as `output_with_timeout()` for tokio, the pipes are drained and the child is waited
+/// for concurrently on the runtime
+#[cfg(feature = "tokio")]
+async fn tokio_output_with_timeout(
+    mut command: tokio::process::Command,
+    timeout: Duration,
+) -> Result<std::process::Output, TimeoutError> {
+    let start = Instant::now();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(TimeoutError::Spawn)?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    let pid = child
+        .id()
+        .expect("a child has an id until it is waited for");
+    let exit = async {
+        match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => (status, false),
+            Err(_) => {
+                terminate_process_tree(pid);
+                let status = match tokio::time::timeout(TERMINATE_GRACE, child.wait()).await {
+                    Ok(status) => status,
+                    Err(_) => {
+                        kill_process_tree(pid);
+                        child.wait().await
+                    }
+                };
+                // descendants ignoring the request would keep the pipes open
+                kill_process_tree(pid);
+                (status, true)
+            }
+        }
+    };
+    let ((status, expired), stdout, stderr) =
+        tokio::join!(exit, read_pipe(stdout), read_pipe(stderr));
+    let output = std::process::Output {
+        status: status.map_err(TimeoutError::Spawn)?,
+        stdout,
+        stderr,
+    };
+
+    if expired {
+        Err(TimeoutError::Expired {
+            timeout,
+            elapsed: start.elapsed(),
+            output,
+        })
+    } else {
+        Ok(output)
+    }
+}
+
+/// Reads a pipe of a tokio child to its end
+#[cfg(feature = "tokio")]
+async fn read_pipe<R: tokio::io::AsyncRead + Unpin>(pipe: Option<R>) -> Vec<u8> {
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer = Vec::new();
+    if let Some(mut pipe) = pipe {
+        let _ = pipe.read_to_end(&mut buffer).await;
+    }
+    buffer
+}
+
+// how long a terminated process gets to exit before it is killed
+const TERMINATE_GRACE: Duration = Duration::from_secs(1);
+
+/// Asks a process and all its descendants to exit ('SIGTERM')
+#[cfg(unix)]
+fn terminate_process_tree(pid: u32) {
+    // the process runs in its own process group with its id as the group id
+    let _ = Command::new("kill")
+        .args(["-TERM", "--", &format!("-{}", pid)])
+        .status();
+}
+
+/// Asks a process and all its descendants to exit
+#[cfg(windows)]
+fn terminate_process_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/T", "/PID", &pid.to_string()])
+        .status();
+}
+
+/// Asks a process and all its descendants to exit
+#[cfg(not(any(unix, windows)))]
+fn terminate_process_tree(_pid: u32) {}
+
+/// Checks each registered path for existing as an executable file
+fn verify_executables(executables: &BTreeMap<String, Utf8PathBuf>) -> VerifyReport {
+    let invalid = executables
+        .iter()
+        .filter_map(|(name, path)| {
+            let problem = match path.metadata() {
+                Err(_) => ArtifactProblem::Missing,
+                Ok(metadata) if !metadata.is_file() => ArtifactProblem::NotAFile,
+                Ok(metadata) if !is_executable(&metadata) => ArtifactProblem::NotExecutable,
+                Ok(_) => return None,
+            };
+            Some(InvalidExecutable {
+                name: name.clone(),
+                path: path.clone(),
+                problem,
+            })
+        })
+        .collect();
+    VerifyReport { invalid }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    true
+}
+
+/// Kills a process and all its descendants, like the build scripts and rustc started by cargo
+#[cfg(unix)]
+fn kill_process_tree(pid: u32) {
+    // the process runs in its own process group with its id as the group id
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", pid)])
+        .status();
+}
+
+/// Kills a process and all its descendants, like the build scripts and rustc started by cargo
+#[cfg(windows)]
+fn kill_process_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .status();
+}
+
+/// Kills a process and all its descendants, like the build scripts and rustc started by cargo
+#[cfg(not(any(unix, windows)))]
+fn kill_process_tree(_pid: u32) {}
+
+/// Checks whether the given cargo is the rustup proxy rather than the binary of a toolchain,
+/// the latter lives in the 'toolchains' directory of the rustup home.
+fn is_rustup_proxy(cargo: &OsStr) -> bool {
+    !Path::new(cargo)
+        .components()
+        .any(|component| component.as_os_str() == "toolchains")
+}
+
+/// Returns the rustup proxy for `program` in the 'bin' directory of the cargo home, `None`
+/// when rustup is not installed there
+fn rustup_proxy(program: &str) -> Option<PathBuf> {
+    let cargo_home = env("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| Path::new(&home).join(".cargo")))?;
+    let proxy = cargo_home
+        .join("bin")
+        .join(format!("{}{}", program, std::env::consts::EXE_SUFFIX));
+    proxy.is_file().then_some(proxy)
+}
+
+/// Checks whether rustup knows the toolchain, assumes it does when rustup is not available
+fn is_toolchain_installed(toolchain: &str) -> bool {
+    let output = match Command::new("rustup").args(["toolchain", "list"]).output() {
+        Ok(output) if output.status.success() => output.stdout,
+        _ => return true,
+    };
+    String::from_utf8_lossy(&output).lines().any(|line| {
+        // 'nightly' matches 'nightly-x86_64-unknown-linux-gnu (default)'
+        let installed = line.split_whitespace().next().unwrap_or_default();
+        installed == toolchain
+            || installed
+                .strip_prefix(toolchain)
+                .is_some_and(|host| host.starts_with('-'))
+    })
+}
+
+/// Checks rustup output for the errors emitted when a toolchain is not installed, newer
+/// rustup versions try to install it first.
+fn is_missing_toolchain(stderr: &str) -> bool {
+    ["is not installed", "syncing channel updates for"]
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// Checks cargo output for the errors emitted when dependencies would need the network
+fn is_offline_failure(stderr: &str) -> bool {
+    [
+        "no matching package named",
+        "unable to update registry",
+        "failed to download",
+        "failed to load source for dependency",
+        "attempting to make an HTTP request, but --offline was specified",
+    ]
+    .iter()
+    .any(|pattern| stderr.contains(pattern))
+}
+
+impl BinTest {
+    /// Runs 'cargo build' and register all build executables.
+    /// Executables are identified by their name, without path and filename extension.
+    /// Panics on any error.
+    pub fn new() -> BinTest {
+        BinTestBuilder::new().build()
+    }
+
+    /// Runs 'cargo build' and register all build executables, returns an error on failure
+    pub fn try_new() -> Result<BinTest, Error> {
+        BinTestBuilder::new().try_build()
+    }
+
+    /// Runs 'cargo build' with the default configuration only once per process, see
+    /// `BinTestBuilder::build_cached()`. Panics on any error.
+    pub fn cached() -> Arc<BinTest> {
+        BinTestBuilder::new().build_cached()
+    }
+
+    /// Starts configuring a build, finish it with `BinTestBuilder::build()` or
+    /// `BinTestBuilder::try_build()`
+    pub fn with() -> BinTestBuilder {
+        BinTestBuilder::new()
+    }
+
+    /// Registers the given executables without running cargo, for testing prebuild or
+    /// installed binaries. Fails with `Error::InvalidExecutables` when a path is not an
+    /// executable file. The profile is empty and `rebuild()` builds with the default
+    /// configuration.
+    pub fn from_paths<I, N, P>(paths: I) -> Result<BinTest, Error>
+    where
+        I: IntoIterator<Item = (N, P)>,
+        N: Into<String>,
+        P: Into<Utf8PathBuf>,
+    {
+        let registry = cache::Registry {
+            executables: paths
+                .into_iter()
+                .map(|(name, path)| (name.into(), path.into()))
+                .collect(),
+            ..Default::default()
+        };
+        let bintest =
+            BinTest::from_registry(registry, &BinTestBuilder::default(), BuildSource::Paths);
+        let report = bintest.verify();
+        if report.is_ok() {
+            Ok(bintest)
+        } else {
+            Err(Error::InvalidExecutables(report))
+        }
+    }
+
+    fn new_with_builder(mut builder: BinTestBuilder) -> Result<BinTest, Error> {
+        builder.validate().map_err(Error::InvalidConfig)?;
+        if cfg!(miri) || env("MIRI_SYSROOT").is_some() {
+            return BinTest::under_miri(&builder);
+        }
+        if let Some(runner) = builder
+            .runner
+            .as_ref()
+            .filter(|runner| !runner.is_installed())
+        {
+            return Err(Error::RunnerNotFound(runner.program.clone()));
+        }
+        builder.prepare()?;
+        if !builder.matrix.is_empty() {
+            return builder.build_matrix();
+        }
+        let start = Instant::now();
+        let metadata = OnceCell::new();
+
+        if let Some(executables) = builder.bin_exe_env(&metadata) {
+            let profile = builder.resolved_profile();
+            let target = builder.effective_target(&metadata, executables.values(), &profile)?;
+            if builder.verbosity != Verbosity::Silent {
+                eprintln!(
+                    "bintest: registered {} executables from CARGO_BIN_EXE_*",
+                    executables.len()
+                );
+            }
+            // the variables are only set for the binaries of the package under test
+            let package = builder
+                .metadata(&metadata)?
+                .root_package()
+                .expect("the variables are set for a package");
+            let infos = executables
+                .keys()
+                .map(|name| {
+                    (
+                        name.clone(),
+                        ExecutableInfo {
+                            package_id: package.id.repr.clone(),
+                            package: package.name.clone(),
+                            kind: String::from("bin"),
+                            // not known, cargo test may have been given other features
+                            features: Vec::new(),
+                            profile: profile.clone(),
+                            #[cfg(feature = "artifact")]
+                            artifact: None,
+                        },
+                    )
+                })
+                .collect();
+            return Ok(BinTest::from_registry(
+                cache::Registry {
+                    executables,
+                    libraries: BTreeMap::new(),
+                    debug_infos: BTreeMap::new(),
+                    infos,
+                    ambiguous: BTreeMap::new(),
+                    profile,
+                    target,
+                },
+                &builder,
+                BuildSource::BinExeEnv,
+            ));
+        }
+
+        let disk_cache = builder.disk_cache(&metadata, &builder.resolved_profile())?;
+        if let Some(registry) = disk_cache
+            .as_ref()
+            .filter(|_| !builder.force_rebuild)
+            .and_then(cache::RegistryCache::load)
+        {
+            let bintest = BinTest::from_registry(registry, &builder, BuildSource::DiskCache);
+            // artifacts removed by 'cargo clean' make it stale
+            if bintest.verify().is_ok() {
+                if builder.verbosity != Verbosity::Silent {
+                    eprintln!(
+                        "bintest: registered {} executables from the cache",
+                        bintest.build_executables.len()
+                    );
+                }
+                return Ok(bintest);
+            }
+        }
+
+        // created before anything else can fail, so that it gets removed on errors as well
+        let ephemeral_dir = if builder.ephemeral_target_dir {
+            let dir = EphemeralDir::create()?;
+            builder.target_dir = Some(dir.path.clone());
+            Some(dir)
+        } else {
+            None
+        };
+
+        let features = builder.features_with_required(&metadata)?;
+        let mut cargo_build = builder.cargo_build_command(&features)?;
+        let invocation = CargoInvocation::from_command(&cargo_build);
+        let profile = builder.resolved_profile();
+        let container_mounts = builder.container_mounts(&metadata)?;
+
+        if !builder.ignore_rust_version {
+            builder.check_rust_version(&metadata)?;
+        }
+        builder.check_requested_targets(&metadata)?;
+        let selected_packages = builder.selected_package_ids(&metadata)?;
+        let lockfile_snapshot = builder.lockfile_snapshot(&metadata)?;
+        let warned_packages: Option<HashSet<PackageId>> = if builder.fail_on_warnings {
+            Some(
+                builder
+                    .metadata(&metadata)?
+                    .workspace_members
+                    .iter()
+                    .cloned()
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        let (_permit, waited_for_build) = BUILD_SEMAPHORE.acquire();
+        // released when dropped at the end of the build
+        let _lock = builder.lock_target_dir(&metadata)?;
+        let build_start = Instant::now();
+        let mut cargo_result = cargo_build.spawn().map_err(Error::Spawn)?;
+
+        let timed_out = Arc::new(AtomicBool::new(false));
+        // dropping the sender when the output is consumed stops the watchdog
+        let (build_finished, watchdog) = mpsc::channel::<()>();
+        if let Some(timeout) = builder.timeout {
+            let pid = cargo_result.id();
+            let timed_out = Arc::clone(&timed_out);
+            std::thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = watchdog.recv_timeout(timeout) {
+                    timed_out.store(true, Ordering::SeqCst);
+                    kill_process_tree(pid);
+                }
+            });
+        }
+
+        // read concurrently to stdout, a full pipe would block cargo otherwise
+        let forward_stderr = !builder.capture_stderr;
+        let stderr_reader = cargo_result.stderr.take().map(|stderr| {
+            std::thread::spawn(move || {
+                let mut captured = String::new();
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    if forward_stderr {
+                        eprintln!("{}", line);
+                    }
+                    captured.push_str(&line);
+                    captured.push('\n');
+                }
+                captured
+            })
+        });
+
+        let mut build_executables = BTreeMap::new();
+        // binaries with their artifact, registered once all are known
+        let mut binaries: Vec<(String, Utf8PathBuf, Artifact)> = Vec::new();
+        // executables of artifact dependencies with their package
+        let mut dependency_binaries: Vec<(String, String, Utf8PathBuf)> = Vec::new();
+        // examples, test and bench harnesses are registered after the binaries under a namespace
+        let mut namespaced = Vec::new();
+        // compiler errors by target
+        let mut failures: BTreeMap<String, String> = BTreeMap::new();
+        // everything the compiler said for `collect_messages()`
+        let mut messages = Vec::new();
+        // rendered warnings of workspace members for `fail_on_warnings()`
+        let mut warnings = Vec::new();
+        // shown on timeouts, the build got stuck after this one
+        let mut last_artifact = None;
+        // targets and executables which were not fresh
+        let mut rebuilt_targets = Vec::new();
+        let mut rebuilt_paths = HashSet::new();
+        let mut fresh_count = 0;
+        // the artifact of every executable by its path
+        let mut executable_artifacts: BTreeMap<Utf8PathBuf, Artifact> = BTreeMap::new();
+        // everything else that was build, to explain empty builds
+        let mut other_artifacts: Vec<(PackageId, String)> = Vec::new();
+        let mut libraries = BTreeMap::new();
+        let mut build_scripts = BTreeMap::new();
+
+        let reader = BufReader::new(cargo_result.stdout.take().unwrap());
+        for message in cargo_metadata::Message::parse_stream(reader) {
+            let message = match message {
+                Ok(message) => message,
+                Err(err) => {
+                    // don't leave a zombie behind, the JSON error is what matters
+                    let _ = cargo_result.kill();
+                    let _ = cargo_result.wait();
+                    return Err(Error::Json(err));
+                }
+            };
+            if let (true, Message::CompilerMessage(message)) = (builder.collect_messages, &message)
+            {
+                messages.push(message.clone());
+            }
+            match message {
+                Message::CompilerMessage(message)
+                    if matches!(
+                        message.message.level,
+                        DiagnosticLevel::Error | DiagnosticLevel::Ice
+                    ) =>
+                {
+                    if let Some(rendered) = &message.message.rendered {
+                        failures
+                            .entry(message.target.name)
+                            .or_default()
+                            .push_str(rendered);
+                    }
+                }
+                Message::CompilerMessage(message)
+                    if message.message.level == DiagnosticLevel::Warning
+                        && warned_packages
+                            .as_ref()
+                            .is_some_and(|packages| packages.contains(&message.package_id)) =>
+                {
+                    // rustc closes with a '1 warning emitted' summary which has no spans
+                    let summary = message.message.spans.is_empty()
+                        && message.message.message.ends_with("emitted");
+                    if let (false, Some(rendered)) = (summary, message.message.rendered) {
+                        warnings.push(rendered);
+                    }
+                }
+                Message::CompilerArtifact(mut artifact) => {
+                    for path in artifact
+                        .executable
+                        .iter_mut()
+                        .chain(artifact.filenames.iter_mut())
+                    {
+                        map_container_path(path, &container_mounts);
+                    }
+                    last_artifact =
+                        Some((artifact.target.name.clone(), artifact.package_id.clone()));
+                    if artifact.fresh {
+                        fresh_count += 1;
+                    } else {
+                        rebuilt_targets.push(artifact.target.name.clone());
+                        if let Some(executable) = &artifact.executable {
+                            rebuilt_paths.insert(executable.clone());
+                        }
+                    }
+                    if let Some(executable) = &artifact.executable {
+                        executable_artifacts.insert(executable.clone(), artifact.clone());
+                    }
+                    // only artifact dependencies give executables of other packages
+                    let dependency = builder.is_dependency(&metadata, &artifact.package_id);
+                    let selected = match &selected_packages {
+                        _ if dependency => {
+                            builder.dependency_artifacts && artifact.executable.is_some()
+                        }
+                        Some(selected) => selected.contains(&artifact.package_id),
+                        None => true,
+                    };
+                    if !selected {
+                        continue;
+                    }
+
+                    if builder.include_libraries {
+                        if let Some(library) = library_file(&artifact) {
+                            libraries.insert(artifact.target.name.clone(), library.clone());
+                        }
+                    }
+                    if artifact.executable.is_none() {
+                        other_artifacts.push((
+                            artifact.package_id.clone(),
+                            format!(
+                                "{} ({})",
+                                artifact.target.name,
+                                artifact.target.kind.join(", ")
+                            ),
+                        ));
+                    }
+
+                    if let Some(executable) = artifact.executable.clone() {
+                        match artifact_kind(&artifact) {
+                            // artifact dependencies keep the hash in their file name
+                            "bin" if dependency => dependency_binaries.push((
+                                artifact.target.name.clone(),
+                                builder.package_name(&metadata, &artifact.package_id),
+                                executable,
+                            )),
+                            "bin" => binaries.push((
+                                String::from(executable.file_stem().expect("filename")),
+                                executable,
+                                artifact,
+                            )),
+                            namespace => {
+                                namespaced.push((namespace, artifact.target.name, executable))
+                            }
+                        }
+                    }
+                }
+                Message::BuildScriptExecuted(script) if builder.collect_build_scripts => {
+                    let member = builder.metadata(&metadata).is_ok_and(|metadata| {
+                        metadata.workspace_members.contains(&script.package_id)
+                    });
+                    if member {
+                        build_scripts
+                            .insert(builder.package_name(&metadata, &script.package_id), script);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut by_name: BTreeMap<String, Vec<(Utf8PathBuf, Artifact)>> = BTreeMap::new();
+        for (name, executable, artifact) in binaries {
+            let candidates = by_name.entry(name).or_default();
+            if !candidates
+                .iter()
+                .any(|(_, candidate)| candidate.package_id == artifact.package_id)
+            {
+                candidates.push((executable, artifact));
+            }
+        }
+
+        let mut ambiguous = BTreeMap::new();
+        for (name, mut candidates) in by_name {
+            if candidates.len() == 1 {
+                let (executable, _) = candidates.pop().expect("one candidate");
+                build_executables.insert(name, executable);
+                continue;
+            }
+
+            let mut packages = Vec::new();
+            for (_, artifact) in &candidates {
+                packages.push(builder.package_name(&metadata, &artifact.package_id));
+            }
+
+            if !builder.qualify_duplicates {
+                // don't leave a zombie behind
+                let _ = cargo_result.kill();
+                let _ = cargo_result.wait();
+                return Err(Error::DuplicateExecutable { name, packages });
+            }
+
+            let qualified: Vec<String> = packages
+                .iter()
+                .map(|package| format!("{}/{}", package, name))
+                .collect();
+            for (qualified, (executable, artifact)) in qualified.iter().zip(candidates) {
+                // cargo uplifts all of them to the same path, the last one build wins there
+                let executable = match builder.deps_executable(
+                    &metadata,
+                    &executable,
+                    &artifact.target.src_path,
+                ) {
+                    Some(deps_executable) => {
+                        if rebuilt_paths.contains(&executable) {
+                            rebuilt_paths.insert(deps_executable.clone());
+                        }
+                        executable_artifacts.insert(deps_executable.clone(), artifact);
+                        deps_executable
+                    }
+                    None => executable,
+                };
+                build_executables.insert(qualified.clone(), executable);
+            }
+            ambiguous.insert(name, qualified);
+        }
+
+        // executables of the workspace keep their bare name
+        for (name, package, executable) in &dependency_binaries {
+            let shared = dependency_binaries
+                .iter()
+                .any(|(other, other_package, _)| other == name && other_package != package);
+            if shared || build_executables.contains_key(name) || ambiguous.contains_key(name) {
+                build_executables.insert(format!("{}/{}", package, name), executable.clone());
+            } else {
+                build_executables.insert(name.clone(), executable.clone());
+            }
+        }
+
+        for (namespace, name, executable) in namespaced {
+            if !build_executables.contains_key(&name) && !ambiguous.contains_key(&name) {
+                build_executables.insert(name.clone(), executable.clone());
+            }
+            build_executables.insert(format!("{}/{}", namespace, name), executable);
+        }
+        build_executables.retain(|name, _| builder.registers(name));
+        ambiguous.retain(|name, _| builder.registers(name));
+
+        let missing: Vec<String> = builder
+            .specific_executables
+            .iter()
+            .filter(|name| !build_executables.contains_key(*name) && !ambiguous.contains_key(*name))
+            .cloned()
+            .collect();
+
+        let status = cargo_result.wait().map_err(Error::Spawn)?;
+        let build_duration = build_start.elapsed();
+        drop(build_finished);
+        let stderr_output = stderr_reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+
+        if timed_out.load(Ordering::SeqCst) {
+            return Err(Error::Timeout {
+                timeout: builder.timeout.unwrap_or_default(),
+                last_artifact: last_artifact.map(|(target, package_id)| {
+                    format!(
+                        "{} ({})",
+                        target,
+                        builder.package_name(&metadata, &package_id)
+                    )
+                }),
+            });
+        }
+
+        if !status.success() {
+            if let Some(toolchain) = &builder.toolchain {
+                if is_missing_toolchain(&stderr_output) {
+                    return Err(Error::ToolchainNotInstalled(toolchain.clone()));
+                }
+            }
+            if builder.offline && is_offline_failure(&stderr_output) {
+                return Err(Error::Offline(status));
+            }
+            if !builder.keep_going {
+                return Err(Error::BuildFailed(BuildError {
+                    status,
+                    output: if builder.capture_stderr {
+                        stderr_output
+                    } else {
+                        String::new()
+                    },
+                    failures: failures
+                        .into_iter()
+                        .map(|(target, diagnostics)| BuildFailure {
+                            target,
+                            diagnostics,
+                        })
+                        .collect(),
+                }));
+            }
+        }
+
+        if !warnings.is_empty() {
+            return Err(Error::Warnings(warnings));
+        }
+
+        if builder.expect_fresh && !rebuilt_targets.is_empty() {
+            return Err(Error::NotFresh(rebuilt_targets));
+        }
+
+        if !missing.is_empty() && !builder.keep_going {
+            return Err(Error::MissingExecutables(missing));
+        }
+
+        if build_executables.is_empty()
+            && libraries.is_empty()
+            && failures.is_empty()
+            && !builder.allow_empty
+        {
+            return Err(builder.no_executables(&metadata, other_artifacts));
+        }
+
+        if let Some((lockfile, contents)) = lockfile_snapshot {
+            if std::fs::read(&lockfile).ok() != contents {
+                return Err(Error::LockfileModified {
+                    lockfile,
+                    mode: if builder.frozen { "frozen" } else { "locked" },
+                });
+            }
+        }
+
+        let target = builder.effective_target(&metadata, build_executables.values(), &profile)?;
+
+        let report = verify_executables(&build_executables);
+        if !report.is_ok() {
+            return Err(Error::InvalidExecutables(report));
+        }
+
+        if builder.verbosity != Verbosity::Silent {
+            eprintln!(
+                "bintest: registered {} executables in {:.1}s",
+                build_executables.len(),
+                start.elapsed().as_secs_f64()
+            );
+        }
+
+        let infos = build_executables
+            .iter()
+            .filter_map(|(name, path)| {
+                let artifact = executable_artifacts.get(path)?;
+                Some((
+                    name.clone(),
+                    ExecutableInfo {
+                        package_id: artifact.package_id.repr.clone(),
+                        package: builder.package_name(&metadata, &artifact.package_id),
+                        kind: String::from(artifact_kind(artifact)),
+                        features: artifact.features.clone(),
+                        profile: profile.clone(),
+                        #[cfg(feature = "artifact")]
+                        artifact: Some(artifact.clone()),
+                    },
+                ))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let debug_infos = build_executables
+            .iter()
+            .filter_map(|(name, path)| {
+                let artifact = executable_artifacts.get(path)?;
+                let debug_info = debug_file(artifact)?;
+                if artifact.executable.as_ref() == Some(path) {
+                    return Some((name.clone(), debug_info.clone()));
+                }
+                // the copy in 'deps' of a qualified duplicate has its own next to it
+                let file_name = format!("{}.{}", path.file_stem()?, debug_info.extension()?);
+                let sibling = path.with_file_name(file_name);
+                sibling.exists().then(|| (name.clone(), sibling))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let registry = cache::Registry {
+            executables: build_executables,
+            libraries,
+            debug_infos,
+            infos,
+            ambiguous,
+            profile,
+            target,
+        };
+        if let Some(disk_cache) = disk_cache {
+            // a failure to write the cache only means building again next time
+            let _ = disk_cache.store(&registry);
+        }
+        let cache::Registry {
+            executables: build_executables,
+            libraries,
+            debug_infos,
+            infos,
+            ambiguous,
+            profile,
+            target,
+        } = registry;
+
+        let rebuilt_executables = build_executables
+            .iter()
+            .filter(|(_, path)| rebuilt_paths.contains(*path))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        Ok(BinTest {
+            ephemeral_dirs: ephemeral_dir.into_iter().collect(),
+            stats: BuildStats {
+                source: BuildSource::Cargo,
+                duration: build_duration,
+                compiled: rebuilt_targets.len(),
+                fresh: fresh_count,
+                cache_hits: 0,
+            },
+            cache_hits: AtomicUsize::new(0),
+            rebuilt: !rebuilt_targets.is_empty(),
+            rebuilt_executables,
+            build_executables,
+            profile,
+            target,
+            waited_for_build,
+            build_output: if builder.capture_stderr {
+                stderr_output
+            } else {
+                String::new()
+            },
+            failed_targets: failures
+                .into_iter()
+                .map(|(target, diagnostics)| BuildFailure {
+                    target,
+                    diagnostics,
+                })
+                .collect(),
+            ambiguous,
+            verify_on_command: builder.verify_on_command,
+            messages,
+            infos,
+            invocation: Some(invocation),
+            runner: OnceLock::new(),
+            libraries,
+            debug_infos,
+            build_scripts,
+            builder,
+        })
+    }
+
+    /// Miri can not run cargo, only the 'CARGO_BIN_EXE_<name>' variables are registered
+    /// then. Paths are dropped when they do not exist, with isolation Miri can not tell.
+    fn under_miri(builder: &BinTestBuilder) -> Result<BinTest, Error> {
+        let executables: BTreeMap<String, Utf8PathBuf> = std::env::vars()
+            .filter_map(|(key, path)| {
+                let name = key.strip_prefix("CARGO_BIN_EXE_")?;
+                let path = Utf8PathBuf::from(path);
+                let missing = path.metadata().map_or_else(
+                    |err| err.kind() == std::io::ErrorKind::NotFound,
+                    |m| !m.is_file(),
+                );
+                (!missing).then(|| (String::from(name), path))
+            })
+            .filter(|(name, _)| {
+                builder.specific_executables.is_empty()
+                    || builder.specific_executables.contains(name)
+            })
+            .filter(|(name, _)| builder.registers(name))
+            .collect();
+        if executables.is_empty() {
+            return Err(Error::UnsupportedUnderMiri);
+        }
+        Ok(BinTest::from_registry(
+            cache::Registry {
+                executables,
+                profile: builder.resolved_profile(),
+                target: builder.target.clone(),
+                ..Default::default()
+            },
+            builder,
+            BuildSource::BinExeEnv,
+        ))
+    }
+
+    /// Combines the builds of a `matrix()`, suffixing all names with the label
+    fn from_matrix(builds: Vec<(String, BinTest)>, builder: BinTestBuilder) -> BinTest {
+        let mut combined = BinTest {
+            build_executables: BTreeMap::new(),
+            profile: String::new(),
+            target: None,
+            waited_for_build: false,
+            failed_targets: Vec::new(),
+            build_output: String::new(),
+            ambiguous: BTreeMap::new(),
+            verify_on_command: false,
+            messages: Vec::new(),
+            rebuilt: false,
+            rebuilt_executables: HashSet::new(),
+            ephemeral_dirs: Vec::new(),
+            stats: BuildStats {
+                source: BuildSource::Cargo,
+                duration: Duration::ZERO,
+                compiled: 0,
+                fresh: 0,
+                cache_hits: 0,
+            },
+            cache_hits: AtomicUsize::new(0),
+            infos: BTreeMap::new(),
+            invocation: None,
+            runner: OnceLock::new(),
+            libraries: BTreeMap::new(),
+            debug_infos: BTreeMap::new(),
+            build_scripts: BTreeMap::new(),
+            builder,
+        };
+
+        for (label, build) in builds {
+            let labeled = |name: &str| format!("{}@{}", name, label);
+            combined.build_executables.extend(
+                build
+                    .build_executables
+                    .into_iter()
+                    .map(|(name, path)| (labeled(&name), path)),
+            );
+            combined
+                .ambiguous
+                .extend(build.ambiguous.into_iter().map(|(name, qualified)| {
+                    (
+                        labeled(&name),
+                        qualified.iter().map(|name| labeled(name)).collect(),
+                    )
+                }));
+            combined
+                .failed_targets
+                .extend(
+                    build
+                        .failed_targets
+                        .into_iter()
+                        .map(|failure| BuildFailure {
+                            target: labeled(&failure.target),
+                            diagnostics: failure.diagnostics,
+                        }),
+                );
+            combined
+                .rebuilt_executables
+                .extend(build.rebuilt_executables.iter().map(|name| labeled(name)));
+            combined.libraries.extend(
+                build
+                    .libraries
+                    .into_iter()
+                    .map(|(name, path)| (labeled(&name), path)),
+            );
+            combined.debug_infos.extend(
+                build
+                    .debug_infos
+                    .into_iter()
+                    .map(|(name, path)| (labeled(&name), path)),
+            );
+            combined.build_scripts.extend(
+                build
+                    .build_scripts
+                    .into_iter()
+                    .map(|(name, script)| (labeled(&name), script)),
+            );
+            combined.infos.extend(
+                build
+                    .infos
+                    .into_iter()
+                    .map(|(name, info)| (labeled(&name), info)),
+            );
+            combined.build_output.push_str(&build.build_output);
+            combined.messages.extend(build.messages);
+            combined.profile = build.profile;
+            combined.target = combined.target.or(build.target);
+            combined.waited_for_build |= build.waited_for_build;
+            combined.verify_on_command = build.verify_on_command;
+            combined.rebuilt |= build.rebuilt;
+            combined.stats.duration += build.stats.duration;
+            combined.stats.compiled += build.stats.compiled;
+            combined.stats.fresh += build.stats.fresh;
+        }
+        combined
+    }
+
+    /// Restores a `BinTest` from the `registry_cache()`
+    fn from_registry(
+        registry: cache::Registry,
+        builder: &BinTestBuilder,
+        source: BuildSource,
+    ) -> BinTest {
+        BinTest {
+            build_executables: registry.executables,
+            profile: registry.profile,
+            target: registry.target,
+            waited_for_build: false,
+            failed_targets: Vec::new(),
+            build_output: String::new(),
+            ambiguous: registry.ambiguous,
+            verify_on_command: builder.verify_on_command,
+            messages: Vec::new(),
+            rebuilt: false,
+            rebuilt_executables: HashSet::new(),
+            ephemeral_dirs: Vec::new(),
+            stats: BuildStats {
+                source,
+                duration: Duration::ZERO,
+                compiled: 0,
+                fresh: 0,
+                cache_hits: 0,
+            },
+            cache_hits: AtomicUsize::new(0),
+            infos: registry.infos,
+            invocation: None,
+            runner: OnceLock::new(),
+            libraries: registry.libraries,
+            debug_infos: registry.debug_infos,
+            build_scripts: BTreeMap::new(),
+            builder: builder.clone(),
+        }
+    }
+
+    /// Gives an `(name, path)` iterator over all executables found
+    pub fn list_executables(&self) -> std::collections::btree_map::Iter<'_, String, Utf8PathBuf> {
+        self.build_executables.iter()
+    }
+
+    /// Gives an `(name, path)` iterator over the libraries registered with
+    /// `BinTestBuilder::include_libraries()`, by target name
+    pub fn list_libraries(&self) -> std::collections::btree_map::Iter<'_, String, Utf8PathBuf> {
+        self.libraries.iter()
+    }
+
+    /// Returns the path of the library build for the target, the shared object ('.so',
+    /// '.dylib' or '.dll') when there is one, the static library otherwise
+    pub fn library_path(&self, name: &str) -> Option<&Utf8Path> {
+        self.libraries.get(name).map(Utf8PathBuf::as_path)
+    }
+
+    /// Returns the debug info cargo produced for the executable: the '.pdb' file on Windows,
+    /// the '.dSYM' bundle on macOS, or the '.dwp' package with `-C split-debuginfo=packed`.
+    /// `None` when the profile produced none or it stays inside the executable, and for
+    /// executables that were not reported by cargo, like with `use_bin_exe_env()`.
+    pub fn debug_info(&self, name: &str) -> Option<&Utf8Path> {
+        self.debug_infos
+            .get(self.registered_name(name)?)
+            .map(Utf8PathBuf::as_path)
+    }
+
+    /// Gives an `(name, path)` iterator over all executables with their exact paths, in the
+    /// form snapshot testing tools take them. With 'trycmd':
+    ///
+    /// ```rust,ignore
+    /// let bins = bintest::BinTest::new();
+    /// let cases = trycmd::TestCases::new();
+    /// for (name, path) in bins.bin_paths() {
+    ///     cases.register_bin(name, path.to_path_buf());
+    /// }
+    /// cases.case("tests/cmd/*.toml");
+    /// ```
+    ///
+    /// The names in the '.toml' and '.md' cases are the registered names then, like
+    /// 'example/demo' for examples.
+    pub fn bin_paths(&self) -> impl Iterator<Item = (&str, &Path)> + '_ {
+        self.build_executables
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_std_path()))
+    }
+
+    /// Lists the registered executables as 'name: path' lines sorted by name, the same on
+    /// every machine and platform, for snapshot tests. See `normalized_entries()`.
+    pub fn normalized_listing(&self) -> String {
+        self.normalized_entries(false)
+            .iter()
+            .map(|entry| format!("{}\n", entry))
+            .collect()
+    }
+
+    /// Returns the registered executables sorted by name with paths relative to the
+    /// profile directory, the target directory and triple are removed. With `profile_dir`
+    /// the path starts with the profile directory, like 'debug/cli'. Paths outside of a
+    /// profile directory are given by their file name.
+    pub fn normalized_entries(&self, profile_dir: bool) -> Vec<ListingEntry> {
+        let profile = crate::profile_dir(&self.profile);
+        self.build_executables
+            .iter()
+            .map(|(name, path)| ListingEntry {
+                name: name.clone(),
+                path: normalized_path(path, profile, profile_dir),
+            })
+            .collect()
+    }
+
+    /// Returns true when an executable with the given name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.registered_name(name).is_some()
+    }
+
+    /// Gives the names of all registered executables in sorted order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.build_executables.keys().map(String::as_str)
+    }
+
+    /// Gives the paths of all registered executables, in the order of their names
+    pub fn paths(&self) -> impl Iterator<Item = &Utf8Path> {
+        self.build_executables.values().map(Utf8PathBuf::as_path)
+    }
+
+    /// Returns the number of registered executables
+    pub fn len(&self) -> usize {
+        self.build_executables.len()
+    }
+
+    /// Returns true when no executables are registered
+    pub fn is_empty(&self) -> bool {
+        self.build_executables.is_empty()
+    }
+
+    /// Looks up a registered executable
+    pub fn get(&self, name: &str) -> Option<Executable<'_>> {
+        self.build_executables
+            .get_key_value(self.registered_name(name)?)
+            .map(|(name, path)| Executable {
+                bintest: self,
+                name,
+                path,
+            })
+    }
+
+    /// Runs the given executable with the arguments and collects its output.
+    /// Panics when there is no such executable.
+    pub fn run<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> std::io::Result<std::process::Output> {
+        self.command(name).args(args).output()
+    }
+
+    /// Runs the given executable with the arguments and returns its stdout, converted
+    /// lossily to UTF-8. Panics with the command line, the exit status and stderr when it
+    /// does not exit successfully, and when there is no such executable.
+    #[track_caller]
+    pub fn run_ok<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> String {
+        run_successfully(self.command(name).args(args))
+    }
+
+    /// Runs the given executable with the arguments and collects its output. When it takes
+    /// longer than `timeout` it is terminated, and killed when it doesn't exit within a
+    /// second then, together with everything it started. The error has the output produced
+    /// until then. Stdin is connected to '/dev/null'.
+    /// Panics when there is no such executable.
+    pub fn run_timeout<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+        timeout: Duration,
+    ) -> Result<std::process::Output, TimeoutError> {
+        output_with_timeout(self.command(name).args(args), timeout)
+    }
+
+    /// Constructs a 'duct::Expression' running the given executable with the arguments,
+    /// configured the same as `command()`. Expressions of several executables compose with
+    /// `pipe()`. Panics when there is no such executable.
+    ///
+    /// ```no_run
+    /// let bins = bintest::BinTest::new();
+    /// let shouted = bins
+    ///     .duct_cmd("producer", ["hello"])
+    ///     .pipe(bins.duct_cmd("upper", Vec::<&str>::new()))
+    ///     .read()
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "duct")]
+    pub fn duct_cmd<I: IntoIterator<Item = S>, S: Into<OsString>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> duct::Expression {
+        let command = self.command(name);
+        let args = command
+            .get_args()
+            .map(OsStr::to_owned)
+            .chain(args.into_iter().map(Into::into));
+        let mut expression = duct::cmd(command.get_program().to_owned(), args);
+        for (key, value) in command.get_envs() {
+            expression = match value {
+                Some(value) => expression.env(key, value),
+                None => expression.env_remove(key),
+            };
+        }
+        if let Some(dir) = command.get_current_dir() {
+            expression = expression.dir(dir);
+        }
+        expression
+    }
+
+    /// Runs the given executables connected like a shell pipeline, stdout of every stage is
+    /// stdin of the next one, `pipeline(&[("producer", &[]), ("consumer", &["-v"])])`. The
+    /// first stage reads from '/dev/null'. The output has stdout of the last stage, stderr
+    /// of all stages in order and the status of the last stage that failed, of the last one
+    /// when all succeeded. Panics when there is no such executable or no stage.
+    pub fn pipeline(&self, stages: &[(&str, &[&str])]) -> std::io::Result<std::process::Output> {
+        assert!(
+            !stages.is_empty(),
+            "bintest: a pipeline needs at least one stage"
+        );
+        let mut commands: Vec<Command> = stages
+            .iter()
+            .map(|(name, args)| {
+                let mut command = self.command_piped(name);
+                command.args(*args);
+                command
+            })
+            .collect();
+
+        let mut children: Vec<std::process::Child> = Vec::new();
+        let mut stdin = Stdio::null();
+        let count = commands.len();
+        for (index, command) in commands.iter_mut().enumerate() {
+            match command.stdin(stdin).spawn() {
+                Ok(mut child) => {
+                    stdin = Stdio::null();
+                    if index + 1 < count {
+                        stdin = child.stdout.take().map_or_else(Stdio::null, Stdio::from);
+                    }
+                    children.push(child);
+                }
+                Err(err) => {
+                    for mut child in children {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        // stderr is read concurrently, a stage filling its pipe would block the pipeline
+        let readers: Vec<_> = children
+            .iter_mut()
+            .map(|child| {
+                let mut stderr = child.stderr.take();
+                std::thread::spawn(move || {
+                    let mut buffer = Vec::new();
+                    if let Some(stderr) = &mut stderr {
+                        let _ = std::io::Read::read_to_end(stderr, &mut buffer);
+                    }
+                    buffer
+                })
+            })
+            .collect();
+        let last = children.pop().expect("at least one stage");
+        let output = last.wait_with_output();
+        // every stage is waited for before reporting an error
+        let statuses: Vec<_> = children.iter_mut().map(std::process::Child::wait).collect();
+        let mut output = output?;
+        let mut statuses = statuses.into_iter().collect::<std::io::Result<Vec<_>>>()?;
+        statuses.push(output.status);
+        output.status = statuses
+            .iter()
+            .rev()
+            .find(|status| !status.success())
+            .copied()
+            .unwrap_or(output.status);
+        for reader in readers {
+            output.stderr.extend(reader.join().unwrap_or_default());
+        }
+        Ok(output)
+    }
+
+    /// Runs the given executable with the arguments like `run()`, the returned future
+    /// completes with its output. The executable is waited for on a thread of its own, so it
+    /// works with tokio, async-std, smol and any other runtime. Dropping the future does not
+    /// stop the executable, `run_tokio()` does.
+    /// Panics when there is no such executable.
+    #[cfg(feature = "async")]
+    pub fn run_async<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> RunHandle<std::io::Result<std::process::Output>> {
+        self.try_run_async(name, args)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Runs the given executable with the arguments like `run_async()`, returns the
+    /// `LookupError` instead of panicking when there is no such executable
+    #[cfg(feature = "async")]
+    pub fn try_run_async<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> Result<RunHandle<std::io::Result<std::process::Output>>, LookupError> {
+        let mut command = self.try_command(name)?;
+        command.args(args);
+        Ok(RunHandle::spawn(move || command.output()))
+    }
+
+    /// Runs the given executable with the arguments like `run_timeout()`, the returned
+    /// future completes with its output or the `TimeoutError`.
+    /// Panics when there is no such executable.
+    #[cfg(feature = "async")]
+    pub fn run_async_timeout<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+        timeout: Duration,
+    ) -> RunHandle<Result<std::process::Output, TimeoutError>> {
+        let mut command = self.command(name);
+        command.args(args);
+        RunHandle::spawn(move || output_with_timeout(&mut command, timeout))
+    }
+
+    /// Constructs a 'tokio::process::Command' for the given executable, configured the same
+    /// as `command()`. The executable is killed when the child is dropped before it exited.
+    /// Panics when there is no such executable.
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// let bins = bintest::BinTest::new();
+    /// let output = bins.tokio_command("cli").arg("--version").output().await.unwrap();
+    /// assert!(output.status.success());
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn tokio_command(&self, name: &str) -> tokio::process::Command {
+        let mut command = tokio::process::Command::from(self.command(name));
+        command.kill_on_drop(true);
+        command
+    }
+
+    /// Runs the given executable with the arguments on the tokio runtime and collects its
+    /// output. The executable is killed when the future is dropped before it finished.
+    /// Panics when there is no such executable.
+    #[cfg(feature = "tokio")]
+    pub fn run_tokio<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> impl std::future::Future<Output = std::io::Result<std::process::Output>> {
+        let mut command = self.tokio_command(name);
+        command.args(args);
+        async move { command.output().await }
+    }
+
+    /// Runs the given executable with the arguments like `run_timeout()` on the tokio
+    /// runtime, the future completes with its output or the `TimeoutError`. The executable
+    /// is killed when the future is dropped before it finished.
+    /// Panics when there is no such executable.
+    #[cfg(feature = "tokio")]
+    pub fn run_tokio_timeout<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+        timeout: Duration,
+    ) -> impl std::future::Future<Output = Result<std::process::Output, TimeoutError>> {
+        let mut command = self.command(name);
+        command.args(args);
+        // in its own process group, so that its children can be terminated as well
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut command = tokio::process::Command::from(command);
+        command.kill_on_drop(true);
+        tokio_output_with_timeout(command, timeout)
+    }
+
+    /// Constructs an 'async_process::Command' for the given executable, configured the same
+    /// as `command()`, for async-std, smol and other runtimes. The executable is killed when
+    /// the child is dropped before it exited.
+    /// Panics when there is no such executable.
+    ///
+    /// ```no_run
+    /// # async fn example() {
+    /// let bins = bintest::BinTest::new();
+    /// let output = bins.async_command("cli").arg("--version").output().await.unwrap();
+    /// assert!(output.status.success());
+    /// # }
+    /// ```
+    #[cfg(feature = "async-process")]
+    pub fn async_command(&self, name: &str) -> async_process::Command {
+        let mut command = async_process::Command::from(self.command(name));
+        command.kill_on_drop(true);
+        command
+    }
+
+    /// Runs the given executable with the arguments through 'async-process' and collects
+    /// its output. The executable is killed when the future is dropped before it finished.
+    /// Panics when there is no such executable.
+    #[cfg(feature = "async-process")]
+    pub fn run_async_process<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> impl std::future::Future<Output = std::io::Result<std::process::Output>> {
+        let mut command = self.async_command(name);
+        command.args(args);
+        async move { command.output().await }
+    }
+
+    /// Constructs an 'assert_cmd::Command' for the given executable, configured the same as
+    /// `command()`, for the assertions of 'assert_cmd'. Unlike 'Command::cargo_bin()' this
+    /// finds examples, other workspace members and builds with other features or profiles.
+    /// Panics when there is no such executable.
+    ///
+    /// ```no_run
+    /// let bins = bintest::BinTest::new();
+    /// bins.assert_command("cli").arg("--help").assert().success();
+    /// ```
+    #[cfg(feature = "assert-cmd")]
+    pub fn assert_command(&self, name: &str) -> assert_cmd::Command {
+        assert_cmd::Command::from_std(self.command(name))
+    }
+
+    /// Gives a 'BINTEST_BIN_<NAME>' variable with the path for every registered executable,
+    /// like the 'CARGO_BIN_EXE_<name>' variables of cargo. The name is uppercased and every
+    /// character other than ASCII letters, digits and '_' becomes a '_', so 'my-tool' gives
+    /// 'BINTEST_BIN_MY_TOOL' and 'example/demo' gives 'BINTEST_BIN_EXAMPLE_DEMO'. Paths are
+    /// converted lossily to UTF-8.
+    pub fn env_vars(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        self.build_executables
+            .iter()
+            .map(|(name, path)| (bin_env_name(name), path.to_string()))
+    }
+
+    /// Constructs a command with the variables of `env_vars()` set, for executables which
+    /// start other executables of the workspace.
+    /// Panics when there is no such executable.
+    pub fn command_with_bin_env(&self, name: &str) -> Command {
+        let mut command = self.command(name);
+        command.envs(self.env_vars());
+        command
+    }
+
+    /// Returns the directories of all registered executables without duplicates, in the
+    /// order of the executable names
+    pub fn bin_dirs(&self) -> Vec<Utf8PathBuf> {
+        let mut dirs: Vec<Utf8PathBuf> = Vec::new();
+        for dir in self
+            .build_executables
+            .values()
+            .filter_map(|path| path.parent())
+        {
+            if !dirs.iter().any(|known| known == dir) {
+                dirs.push(dir.to_owned());
+            }
+        }
+        dirs
+    }
+
+    /// Prepends the `bin_dirs()` to a 'PATH' value, with the separator of the platform.
+    /// Panics when a directory contains the separator.
+    pub fn path_env(&self, existing: Option<&OsStr>) -> OsString {
+        let dirs = self
+            .bin_dirs()
+            .into_iter()
+            .map(Utf8PathBuf::into_std_path_buf);
+        let existing = existing.into_iter().flat_map(std::env::split_paths);
+        std::env::join_paths(dirs.chain(existing))
+            .unwrap_or_else(|err| panic!("bintest: can not extend PATH: {}", err))
+    }
+
+    /// Constructs a command with the `bin_dirs()` prepended to 'PATH', for scripts and tools
+    /// which find the executables by name.
+    /// Panics when there is no such executable.
+    pub fn command_with_path(&self, name: &str) -> Command {
+        let mut command = self.command(name);
+        command.env("PATH", self.path_env(env("PATH").as_deref()));
+        command
+    }
+
+    /// Constructs a command that runs attached to a pseudo terminal, for testing what the
+    /// executable does when stdio is a terminal. Needs the 'pty' feature.
+    /// Panics when there is no such executable.
+    #[cfg(all(feature = "pty", unix))]
+    pub fn command_pty(&self, name: &str) -> PtyCommand {
+        PtyCommand::new(self.command(name))
+    }
+
+    /// Spawns the given executable with the arguments, it is killed together with everything
+    /// it started when the guard is dropped, also when the test panics.
+    /// Panics when there is no such executable.
+    pub fn spawn_guarded<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(
+        &self,
+        name: &str,
+        args: I,
+    ) -> std::io::Result<ChildGuard> {
+        ChildGuard::spawn(self.command(name).args(args))
+    }
+
+    /// Gives the registered executables whose name matches the glob pattern, '*' matches any
+    /// number of characters and '?' a single one. Matching is case-sensitive.
+    pub fn executables_matching<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> Result<impl Iterator<Item = (&'a str, &'a Utf8Path)>, PatternError> {
+        self.matching(pattern, false)
+    }
+
+    /// Same as `executables_matching()` but ignores the case
+    pub fn executables_matching_ignore_case<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> Result<impl Iterator<Item = (&'a str, &'a Utf8Path)>, PatternError> {
+        self.matching(pattern, true)
+    }
+
+    fn matching<'a>(
+        &'a self,
+        pattern: &'a str,
+        ignore_case: bool,
+    ) -> Result<impl Iterator<Item = (&'a str, &'a Utf8Path)>, PatternError> {
+        check_pattern(pattern)?;
+        Ok(self
+            .build_executables
+            .iter()
+            .filter(move |(name, _)| glob_match(pattern, name, ignore_case))
+            .map(|(name, path)| (name.as_str(), path.as_path())))
+    }
+
+    /// Gives a command for every registered executable, in the order of their names
+    pub fn commands(&self) -> impl Iterator<Item = (&str, Command)> {
+        self.names().map(move |name| (name, self.command(name)))
+    }
+
+    /// Runs every registered executable with the same arguments, for checking that all of
+    /// them behave consistently like on '--version'
+    pub fn run_all(
+        &self,
+        args: &[&str],
+    ) -> BTreeMap<String, std::io::Result<std::process::Output>> {
+        self.commands()
+            .map(|(name, mut command)| (String::from(name), command.args(args).output()))
+            .collect()
+    }
+
+    /// Same as `run_all()` but kills executables that take longer than `timeout`, their
+    /// result is an error of kind `TimedOut`
+    pub fn run_all_timeout(
+        &self,
+        args: &[&str],
+        timeout: Duration,
+    ) -> BTreeMap<String, std::io::Result<std::process::Output>> {
+        self.commands()
+            .map(|(name, mut command)| {
+                let output =
+                    output_with_timeout(command.args(args), timeout).map_err(|err| match err {
+                        TimeoutError::Spawn(err) => err,
+                        expired => std::io::Error::new(std::io::ErrorKind::TimedOut, expired),
+                    });
+                (String::from(name), output)
+            })
+            .collect()
+    }
+
+    /// Returns the cargo command line the executables were build with, `None` when they
+    /// did not come from running cargo
+    pub fn cargo_invocation(&self) -> Option<&CargoInvocation> {
+        self.invocation.as_ref()
+    }
+
+    /// Returns an overview of the build for printing at the start of a test suite
+    pub fn summary(&self) -> BuildSummary {
+        let paths: HashSet<&Utf8PathBuf> = self.build_executables.values().collect();
+        BuildSummary {
+            profile: self.profile.clone(),
+            target: self.target.clone(),
+            executables: self.build_executables.len(),
+            total_size: paths
+                .into_iter()
+                .filter_map(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len())
+                .sum(),
+            duration: self.stats.duration,
+        }
+    }
+
+    /// Returns what cargo reported about a registered executable
+    pub fn info(&self, name: &str) -> Option<&ExecutableInfo> {
+        self.infos.get(self.registered_name(name)?)
+    }
+
+    /// Looks up a registered executable, the handle gives its path, package and commands
+    pub fn executable(&self, name: &str) -> Option<Executable<'_>> {
+        self.get(name)
+    }
+
+    /// Looks up the executable `name` of the given package, same as `get("package::name")`.
+    /// This finds executables of artifact dependencies as well.
+    pub fn executable_in_package(&self, package: &str, name: &str) -> Option<Executable<'_>> {
+        self.get(&format!("{}::{}", package, name))
+    }
+
+    /// Returns the path cargo reported for the given executable
+    pub fn executable_path(&self, name: &str) -> Option<&Utf8Path> {
+        self.build_executables
+            .get(self.registered_name(name)?)
+            .map(Utf8PathBuf::as_path)
+    }
+
+    /// Returns the path cargo reported for the given executable.
+    /// Panics when there is no such executable.
+    pub fn expect_path(&self, name: &str) -> &Utf8Path {
+        self.lookup(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Returns the path of the given executable as a `std::path::Path`, for APIs which do not
+    /// take UTF-8 paths
+    pub fn executable_path_os(&self, name: &str) -> Option<&Path> {
+        self.build_executables
+            .get(self.registered_name(name)?)
+            .map(|path| path.as_std_path())
+    }
+
+    /// Returns the name of the cargo profile the executables were build with
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// Returns the target triple the executables were build for, `None` for the host.
+    /// This also reflects 'CARGO_BUILD_TARGET' and 'build.target' from the cargo configuration.
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// Returns true when the build had to wait for other bintest builds in this process
+    pub fn waited_for_build(&self) -> bool {
+        self.waited_for_build
+    }
+
+    /// Sets how many bintest driven 'cargo build' may run concurrently in this process,
+    /// defaults to one. Others wait until a running build finished.
+    pub fn set_concurrent_builds(builds: usize) {
+        MAX_CONCURRENT_BUILDS.store(builds.max(1), Ordering::Relaxed);
+        BUILD_SEMAPHORE.released.notify_all();
+    }
+
+    /// Returns the stderr output of cargo when `capture_stderr()` was set, empty otherwise
+    pub fn build_output(&self) -> &str {
+        &self.build_output
+    }
+
+    /// Returns the targets that failed to build in `keep_going()` mode
+    pub fn failed_targets(&self) -> &[BuildFailure] {
+        &self.failed_targets
+    }
+
+    /// Keeps the directory of `ephemeral_target_dir()` for debugging instead of removing it
+    /// on drop, returns its path. After `merge()` all directories are kept and the first one
+    /// is returned.
+    pub fn keep(&mut self) -> Option<&Utf8Path> {
+        for dir in &mut self.ephemeral_dirs {
+            dir.keep = true;
+        }
+        self.ephemeral_dirs.first().map(|dir| dir.path.as_path())
+    }
+
+    /// Runs the build again with the same configuration and replaces the registered
+    /// executables, for tests which change the sources. Cargo is always run, neither the
+    /// `registry_cache()` nor 'CARGO_BIN_EXE_<name>' is used. The directory of
+    /// `ephemeral_target_dir()` is reused, so that only the changes are recompiled.
+    pub fn rebuild(&mut self) -> Result<RebuildReport, Error> {
+        let mut builder = self.builder.clone().use_bin_exe_env(false).force_rebuild();
+        if let Some(dir) = self.ephemeral_dirs.first() {
+            builder.ephemeral_target_dir = false;
+            builder.target_dir = Some(dir.path.clone());
+        }
+        let mut rebuilt = builder.try_build()?;
+
+        let mut recompiled: Vec<String> = rebuilt.rebuilt_executables.iter().cloned().collect();
+        recompiled.sort();
+        let moved = rebuilt
+            .build_executables
+            .iter()
+            .filter(|(name, path)| {
+                self.build_executables
+                    .get(*name)
+                    .is_some_and(|old| old != *path)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let builder = std::mem::take(&mut self.builder);
+        rebuilt.ephemeral_dirs = std::mem::take(&mut self.ephemeral_dirs);
+        *self = BinTest { builder, ..rebuilt };
+        Ok(RebuildReport { recompiled, moved })
+    }
+
+    /// Combines the executables of two builds, for example of the workspace and a fixture
+    /// crate, into one registry. Executables and libraries registered in both with different
+    /// paths and build scripts of the same package with different output are an error, see
+    /// `merge_with()` for other policies. `rebuild()` rebuilds only `self`.
+    pub fn merge(self, other: BinTest) -> Result<BinTest, MergeError> {
+        self.merge_with(other, MergePolicy::Fail)
+    }
+
+    /// Combines the executables of two builds, names registered in both with different
+    /// paths are handled by `policy`
+    pub fn merge_with(
+        mut self,
+        other: BinTest,
+        policy: MergePolicy,
+    ) -> Result<BinTest, MergeError> {
+        // no `..`, a new field has to be merged or explicitly left out here
+        let BinTest {
+            build_executables,
+            profile,
+            target,
+            waited_for_build,
+            failed_targets,
+            build_output,
+            ambiguous,
+            messages,
+            rebuilt: was_rebuilt,
+            rebuilt_executables,
+            ephemeral_dirs,
+            stats,
+            mut infos,
+            libraries,
+            mut debug_infos,
+            build_scripts,
+            // the settings, cache and configuration of `self` are kept
+            verify_on_command: _,
+            cache_hits: _,
+            invocation: _,
+            runner: _,
+            builder: _,
+        } = other;
+
+        let collisions: Vec<String> = build_executables
+            .iter()
+            .filter(|(name, path)| {
+                self.build_executables
+                    .get(*name)
+                    .is_some_and(|own| own != *path)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        let library_collisions: Vec<String> = libraries
+            .iter()
+            .filter(|(name, path)| self.libraries.get(*name).is_some_and(|own| own != *path))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let script_collisions: Vec<String> = build_scripts
+            .iter()
+            .filter(|(package, script)| {
+                self.build_scripts
+                    .get(*package)
+                    .is_some_and(|own| own != *script)
+            })
+            .map(|(package, _)| package.clone())
+            .collect();
+        if policy == MergePolicy::Fail
+            && !(collisions.is_empty()
+                && library_collisions.is_empty()
+                && script_collisions.is_empty())
+        {
+            return Err(MergeError {
+                names: collisions,
+                libraries: library_collisions,
+                build_scripts: script_collisions,
+            });
+        }
+
+        let mut unqualified = Vec::new();
+        for (name, path) in build_executables {
+            let info = infos.remove(&name);
+            let debug_info = debug_infos.remove(&name);
+            let rebuilt = rebuilt_executables.contains(&name);
+            if !collisions.contains(&name) {
+                self.insert_executable(name, path, info, debug_info, rebuilt);
+                continue;
+            }
+
+            match policy {
+                MergePolicy::Fail | MergePolicy::KeepFirst => {}
+                MergePolicy::KeepLast => {
+                    self.infos.remove(&name);
+                    self.debug_infos.remove(&name);
+                    self.rebuilt_executables.remove(&name);
+                    self.insert_executable(name, path, info, debug_info, rebuilt);
+                }
+                MergePolicy::Qualify => {
+                    let own_package = self.infos.get(&name).map(|info| info.package.clone());
+                    let other_package = info.as_ref().map(|info| info.package.clone());
+                    let (own_package, other_package) = match (own_package, other_package) {
+                        (Some(own), Some(other)) if own != other => (own, other),
+                        _ => {
+                            unqualified.push(name);
+                            continue;
+                        }
+                    };
+                    let own_name = format!("{}/{}", own_package, name);
+                    let other_name = format!("{}/{}", other_package, name);
+                    if self.build_executables.contains_key(&own_name)
+                        || self.build_executables.contains_key(&other_name)
+                    {
+                        unqualified.push(name);
+                        continue;
+                    }
+
+                    let own_path = self.build_executables.remove(&name).expect("collision");
+                    let own_info = self.infos.remove(&name);
+                    let own_debug_info = self.debug_infos.remove(&name);
+                    let own_rebuilt = self.rebuilt_executables.remove(&name);
+                    self.insert_executable(
+                        own_name.clone(),
+                        own_path,
+                        own_info,
+                        own_debug_info,
+                        own_rebuilt,
+                    );
+                    self.insert_executable(other_name.clone(), path, info, debug_info, rebuilt);
+                    self.ambiguous.insert(name, vec![own_name, other_name]);
+                }
+            }
+        }
+        for (name, path) in libraries {
+            if !library_collisions.contains(&name) || policy == MergePolicy::KeepLast {
+                self.libraries.insert(name, path);
+            }
+        }
+        for (package, script) in build_scripts {
+            if !script_collisions.contains(&package) || policy == MergePolicy::KeepLast {
+                self.build_scripts.insert(package, script);
+            }
+        }
+        // only `Qualify` gets here with collisions it could not resolve
+        if policy == MergePolicy::Qualify
+            && !(unqualified.is_empty()
+                && library_collisions.is_empty()
+                && script_collisions.is_empty())
+        {
+            return Err(MergeError {
+                names: unqualified,
+                libraries: library_collisions,
+                build_scripts: script_collisions,
+            });
+        }
+
+        for (name, qualified) in ambiguous {
+            if !self.build_executables.contains_key(&name) {
+                self.ambiguous.entry(name).or_default().extend(qualified);
+            }
+        }
+        self.failed_targets.extend(failed_targets);
+        self.build_output.push_str(&build_output);
+        self.messages.extend(messages);
+        self.ephemeral_dirs.extend(ephemeral_dirs);
+        if self.profile.is_empty() {
+            self.profile = profile;
+        }
+        self.target = self.target.or(target);
+        self.waited_for_build |= waited_for_build;
+        self.rebuilt |= was_rebuilt;
+        self.stats.duration += stats.duration;
+        self.stats.compiled += stats.compiled;
+        self.stats.fresh += stats.fresh;
+        Ok(self)
+    }
+
+    /// Registers one executable, used when combining builds
+    fn insert_executable(
+        &mut self,
+        name: String,
+        path: Utf8PathBuf,
+        info: Option<ExecutableInfo>,
+        debug_info: Option<Utf8PathBuf>,
+        rebuilt: bool,
+    ) {
+        if let Some(info) = info {
+            self.infos.insert(name.clone(), info);
+        }
+        if let Some(debug_info) = debug_info {
+            self.debug_infos.insert(name.clone(), debug_info);
+        }
+        if rebuilt {
+            self.rebuilt_executables.insert(name.clone());
+        }
+        self.build_executables.insert(name, path);
+    }
+
+    /// Returns how long the build took and how much it had to compile
+    pub fn build_stats(&self) -> BuildStats {
+        BuildStats {
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            ..self.stats
+        }
+    }
+
+    /// Returns true when the build had to compile anything, false when everything was fresh
+    pub fn was_rebuilt(&self) -> bool {
+        self.rebuilt
+    }
+
+    /// Returns whether the given executable was up to date before the build, `None` when
+    /// there is no such executable
+    pub fn is_fresh(&self, name: &str) -> Option<bool> {
+        self.registered_name(name)
+            .map(|name| !self.rebuilt_executables.contains(name))
+    }
+
+    /// Returns the compiler messages of the build when `collect_messages()` was set, empty
+    /// otherwise
+    pub fn messages(&self) -> &[CompilerMessage] {
+        &self.messages
+    }
+
+    /// Returns what the build script of the workspace member reported when
+    /// `collect_build_scripts()` was set: its cfgs, environment and linked libraries
+    pub fn build_script(&self, package: &str) -> Option<&BuildScript> {
+        self.build_scripts.get(package)
+    }
+
+    /// Returns the 'OUT_DIR' the build script of the workspace member wrote its files to,
+    /// see `build_script()`
+    pub fn out_dir(&self, package: &str) -> Option<&Utf8Path> {
+        self.build_script(package)
+            .map(|script| script.out_dir.as_path())
+    }
+
+    /// Returns the collected compiler warnings for the target with the given name
+    pub fn warnings_for<'a>(
+        &'a self,
+        target: &'a str,
+    ) -> impl Iterator<Item = &'a CompilerMessage> + 'a {
+        self.messages.iter().filter(move |message| {
+            message.target.name == target && message.message.level == DiagnosticLevel::Warning
+        })
+    }
+
+    /// Checks that all registered executables still exist and are executable, this is done
+    /// after the build as well
+    pub fn verify(&self) -> VerifyReport {
+        verify_executables(&self.build_executables)
+    }
+
+    /// Resolves 'package::name' to the name the executable of that package is registered
+    /// with, which is 'package/name' when the bare name is ambiguous. Other names are
+    /// returned unchanged.
+    fn resolve_name<'a>(&'a self, name: &'a str) -> &'a str {
+        if self.build_executables.contains_key(name) {
+            return name;
+        }
+        let (package, bare) = match name.split_once("::") {
+            Some(split) => split,
+            None => return name,
+        };
+        let qualified = format!("{}/{}", package, bare);
+        self.build_executables
+            .keys()
+            .find(|registered| {
+                (**registered == qualified || *registered == bare)
+                    && self
+                        .infos
+                        .get(*registered)
+                        .is_some_and(|info| info.package == package)
+            })
+            .map_or(name, String::as_str)
+    }
+
+    /// Returns the name `name` is registered with, after `resolve_name()` and with
+    /// `lenient_lookup()` accepting a near miss. All lookups by name go through this.
+    fn registered_name<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        let resolved = self.resolve_name(name);
+        if self.build_executables.contains_key(resolved) {
+            Some(resolved)
+        } else if self.builder.resolved_lenient_lookup() {
+            self.near_miss(name)
+        } else {
+            None
+        }
+    }
+
+    /// Looks up the path of a registered executable
+    fn lookup(&self, name: &str) -> Result<&Utf8PathBuf, LookupError> {
+        let near_miss = self.near_miss(name);
+        let error = |removed| LookupError {
+            name: String::from(name),
+            available: self.build_executables.keys().cloned().collect(),
+            ambiguous: self.ambiguous.get(name).cloned().unwrap_or_default(),
+            build_failure: self
+                .failed_targets
+                .iter()
+                .find(|failure| failure.target == name)
+                .cloned()
+                .map(Box::new),
+            removed,
+            excluded: self.builder.excludes_executable(name),
+            suggestion: near_miss.map(String::from),
+        };
+
+        let path = match self.registered_name(name) {
+            Some(registered) => &self.build_executables[registered],
+            None => return Err(error(false)),
+        };
+        if self.verify_on_command && !path.is_file() {
+            return Err(error(true));
+        }
+        Ok(path)
+    }
+
+    /// Returns the registered name that matches `name` without a trailing '.exe' or '.bat'
+    /// and ignoring case, when there is exactly one
+    fn near_miss(&self, name: &str) -> Option<&str> {
+        let lower = name.to_lowercase();
+        let stem = lower
+            .strip_suffix(".exe")
+            .or_else(|| lower.strip_suffix(".bat"))
+            .unwrap_or(&lower);
+        let mut matches = self
+            .build_executables
+            .keys()
+            .filter(|registered| registered.to_lowercase() == stem && *registered != name);
+        match (matches.next(), matches.next()) {
+            (Some(registered), None) => Some(registered),
+            _ => None,
+        }
+    }
+
+    /// Constructs a 'std::process::Command' for the given executable name. Executables of a
+    /// specific package can be given as 'package::name'. Nothing else is configured unless
+    /// `BinTestBuilder::sanitize_env()` was set, see `command_piped()`, `command_inherit()`
+    /// and `command_null()` for commands with their stdio set up. When there is a `runner()`
+    /// the command starts the runner with the executable as argument.
+    /// Panics when there is no such executable.
+    pub fn command(&self, name: &str) -> Command {
+        self.try_command(name)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Constructs a 'std::process::Command' for the given executable name
+    pub fn try_command(&self, name: &str) -> Result<Command, LookupError> {
+        self.new_command(
+            name,
+            self.builder.sanitize_env.then_some(&[]),
+            self.runner(),
+        )
+    }
+
+    /// Constructs a command like `command()` which starts the executable itself, also when
+    /// there is a `runner()`.
+    /// Panics when there is no such executable.
+    pub fn command_unwrapped(&self, name: &str) -> Command {
+        self.new_command(name, self.builder.sanitize_env.then_some(&[]), None)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Constructs a command like `command()` which starts the executable under the given
+    /// runner instead of `runner()`, e.g. `command_under("cli", &Runner::valgrind())`.
+    /// Arguments added to the command go to the executable. Panics when there is no such
+    /// executable or the runner is not installed, see `try_command_under()` for skipping.
+    pub fn command_under(&self, name: &str, runner: &Runner) -> Command {
+        self.try_command_under(name, runner)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Constructs a command like `command_under()`, tests can be skipped on
+    /// `Error::RunnerNotFound` when the runner is not installed
+    pub fn try_command_under(&self, name: &str, runner: &Runner) -> Result<Command, Error> {
+        if !runner.is_installed() {
+            return Err(Error::RunnerNotFound(runner.program.clone()));
+        }
+        self.new_command(name, self.builder.sanitize_env.then_some(&[]), Some(runner))
+            .map_err(Error::Lookup)
+    }
+
+    /// Returns the program the commands start the executables with, `BinTestBuilder::runner()`
+    /// or else the runner cargo would use for the target. That is 'CARGO_TARGET_<TRIPLE>_RUNNER'
+    /// or 'target.<triple>.runner' from the cargo configuration. No runner is looked up when
+    /// building for the host without an explicit target.
+    /// WebAssembly targets like 'wasm32-wasip1' default to 'wasmtime run --dir . --', see
+    /// `BinTestBuilder::wasm_dir()`. `executable_path()` gives the '.wasm' file for
+    /// embedding a runtime instead.
+    pub fn runner(&self) -> Option<&Runner> {
+        self.runner
+            .get_or_init(|| match &self.builder.runner {
+                Some(runner) => Some(runner.clone()),
+                None => {
+                    let target = self.target.as_deref()?;
+                    runner::discover(target).or_else(|| {
+                        (target.starts_with("wasm32-") || target.starts_with("wasm64-"))
+                            .then(|| self.builder.wasm_runner())
+                    })
+                }
+            })
+            .as_ref()
+    }
+
+    /// Constructs a command with the `command_env()` presets applied, the build variables
+    /// but the ones to keep are removed before when `sanitize` is given
+    fn new_command(
+        &self,
+        name: &str,
+        sanitize: Option<&[&str]>,
+        runner: Option<&Runner>,
+    ) -> Result<Command, LookupError> {
+        let path = self.lookup(name)?;
+        let mut command = match runner {
+            Some(runner) => runner.command(path.as_std_path()),
+            None => Command::new(path),
+        };
+        if let Some(keep) = sanitize {
+            remove_build_variables(&mut command, keep);
+        }
+        if let Some(profile_file) = self.builder.coverage_profile_file() {
+            command.env("LLVM_PROFILE_FILE", profile_file);
+        }
+        if let Some(sanitizer) = self.builder.sanitizer {
+            command.env(sanitizer.options_variable(), sanitizer.options());
+        }
+        for (key, value) in &self.builder.command_env {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        Ok(command)
+    }
+
+    /// Constructs a command without the variables cargo and rustup set for building and
+    /// testing like 'CARGO_MANIFEST_DIR', 'CARGO_PKG_NAME', 'RUSTUP_TOOLCHAIN' or 'OUT_DIR' in
+    /// its environment, so that the executable behaves the same as when started by a user.
+    /// 'PATH', 'HOME', 'CARGO_HOME', 'RUSTFLAGS' and everything else is kept.
+    /// Panics when there is no such executable.
+    pub fn command_sanitized(&self, name: &str) -> Command {
+        self.command_sanitized_keeping(name, &[])
+    }
+
+    /// Same as `command_sanitized()` but keeps the given variables
+    pub fn command_sanitized_keeping(&self, name: &str, keep: &[&str]) -> Command {
+        self.new_command(name, Some(keep), self.runner())
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Constructs a command with stdin from '/dev/null' and stdout and stderr piped, for
+    /// capturing the output with `spawn()` and `wait_with_output()`.
+    /// Panics when there is no such executable.
+    pub fn command_piped(&self, name: &str) -> Command {
+        let mut command = self.command(name);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+
+    /// Constructs a command which inherits stdin, stdout and stderr from the test.
+    /// Panics when there is no such executable.
+    pub fn command_inherit(&self, name: &str) -> Command {
+        let mut command = self.command(name);
+        command
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        command
+    }
+
+    /// Constructs a command with stdin, stdout and stderr connected to '/dev/null'.
+    /// Panics when there is no such executable.
+    pub fn command_null(&self, name: &str) -> Command {
+        let mut command = self.command(name);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        command
+    }
+
+    /// Constructs a command which runs in the given directory. The directory is only
+    /// borrowed, a temporary directory given as `&tempdir` stays alive for the test.
+    /// Panics when the directory does not exist or there is no such executable.
+    #[track_caller]
+    pub fn command_in<D: TestDir + ?Sized>(&self, name: &str, dir: &D) -> Command {
+        let dir = dir.test_dir();
+        if !dir.is_dir() {
+            panic!(
+                "bintest: working directory <<{}>> for <<{}>> does not exist",
+                dir.display(),
+                name
+            );
+        }
+        let mut command = self.command(name);
+        command.current_dir(dir);
+        command
+    }
+
+    /// Same as `command_in()` but canonicalizes the directory first, so that relative
+    /// fixture paths and symlinks resolve the same on all platforms
+    #[track_caller]
+    pub fn command_in_canonical<D: TestDir + ?Sized>(&self, name: &str, dir: &D) -> Command {
+        let dir = dir.test_dir();
+        let canonical = dir.canonicalize().unwrap_or_else(|err| {
+            panic!(
+                "bintest: working directory <<{}>> for <<{}>> does not exist: {}",
+                dir.display(),
+                name,
+                err
+            )
+        });
+        self.command_in(name, &canonical)
+    }
+
+    /// Constructs a 'std::process::Command' for the executable `name` of the given package,
+    /// same as `command("package::name")`. Panics when there is no such executable.
+    pub fn command_in_package(&self, package: &str, name: &str) -> Command {
+        self.command(&format!("{}::{}", package, name))
+    }
+
+    /// Constructs a 'std::process::Command' for the executable `name` of the given package,
+    /// same as `try_command("package::name")`
+    pub fn try_command_in_package(
+        &self,
+        package: &str,
+        name: &str,
+    ) -> Result<Command, LookupError> {
+        self.try_command(&format!("{}::{}", package, name))
+    }
+}
+
+impl Default for BinTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::{
+        bin_env_name, cache, edit_distance, glob_match, is_build_variable, is_offline_failure,
+        map_container_path, normalized_path, shell_quote, strip_ansi, BTreeMap, BinTest,
+        BinTestBuilder, BuildScript, BuildSource, Duration, ExecutableInfo, MergePolicy, OnceCell,
+        Package, Sanitizer, Utf8Path, Utf8PathBuf,
+    };
+
+    fn dry_run_args(builder: BinTestBuilder) -> Vec<String> {
+        builder
+            .dry_run()
+            .expect("valid configuration")
+            .args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn contains(args: &[String], expected: &[&str]) -> bool {
+        args.windows(expected.len())
+            .any(|window| window == expected)
+    }
+
+    #[test]
+    fn dry_run_features() {
+        let args = dry_run_args(BinTest::with().features(["server", "tls"]));
+        assert!(contains(&args, &["--features", "server,tls"]), "{:?}", args);
+    }
+
+    #[test]
+    fn dry_run_profile() {
+        let args = dry_run_args(BinTest::with().profile("dist"));
+        assert!(contains(&args, &["--profile", "dist"]), "{:?}", args);
+
+        let args = dry_run_args(BinTest::with().release(true));
+        assert!(args.iter().any(|arg| arg == "--release"), "{:?}", args);
+    }
+
+    #[test]
+    fn dry_run_exclude() {
+        let args = dry_run_args(
+            BinTest::with()
+                .build_workspace(true)
+                .exclude("fuzz")
+                .exclude("xtask"),
+        );
+        assert!(args.iter().any(|arg| arg == "--workspace"), "{:?}", args);
+        assert!(contains(&args, &["--exclude", "fuzz"]), "{:?}", args);
+        assert!(contains(&args, &["--exclude", "xtask"]), "{:?}", args);
+    }
+
+    #[test]
+    fn dry_run_cargo_arg_comes_last() {
+        let args = dry_run_args(
+            BinTest::with()
+                .cargo_arg("--timings")
+                .cargo_args(["--message-format", "short"]),
+        );
+        assert_eq!(args.last().map(String::as_str), Some("--timings"));
+        assert!(contains(&args, &["--message-format", "json"]), "{:?}", args);
+        assert!(!args.iter().any(|arg| arg == "short"), "{:?}", args);
+    }
+
+    #[test]
+    fn dry_run_keep_going() {
+        let args = dry_run_args(BinTest::with().keep_going(true));
+        assert!(args.iter().any(|arg| arg == "--keep-going"), "{:?}", args);
+    }
+
+    #[test]
+    fn dry_run_undefined_sanitizer() {
+        let invocation = BinTest::with()
+            .sanitizer(Sanitizer::Undefined)
+            .dry_run()
+            .expect("valid configuration");
+        let env = |name: &str| {
+            invocation
+                .env
+                .iter()
+                .find(|(key, _)| key == name)
+                .and_then(|(_, value)| value.as_ref())
+                .map(|value| value.to_string_lossy().into_owned())
+        };
+
+        let rustflags = env("CARGO_ENCODED_RUSTFLAGS").expect("rustflags are set");
+        assert!(rustflags.contains("-Clink-arg=-fsanitize=undefined"));
+        assert!(!rustflags.contains("-Zsanitizer"));
+
+        let cflags = invocation
+            .env
+            .iter()
+            .filter(|(key, _)| key.to_string_lossy().starts_with("CFLAGS_"))
+            .filter_map(|(_, value)| value.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(cflags.len(), 1);
+        assert!(cflags[0]
+            .to_string_lossy()
+            .ends_with("-fsanitize=undefined"));
+    }
+
+    #[test]
+    fn dry_run_offline() {
+        let args = dry_run_args(BinTest::with().offline(true));
+        assert!(args.iter().any(|arg| arg == "--offline"), "{:?}", args);
+    }
+
+    #[test]
+    fn offline_failures() {
+        assert!(is_offline_failure(
+            "error: no matching package named `serde` found\n\
+             location searched: crates.io index\n"
+        ));
+        assert!(is_offline_failure(
+            "error: failed to download `serde v1.0.0`\n\
+             Caused by:\n  attempting to make an HTTP request, but --offline was specified\n"
+        ));
+        assert!(!is_offline_failure(
+            "error[E0425]: cannot find value `x` in this scope\n"
+        ));
+    }
+
+    #[test]
+    fn dry_run_all_bins() {
+        let args = dry_run_args(BinTest::with().all_bins(true).package("cli"));
+        assert!(args.iter().any(|arg| arg == "--bins"), "{:?}", args);
+        assert!(contains(&args, &["-p", "cli"]), "{:?}", args);
+        assert!(!args.iter().any(|arg| arg == "--lib" || arg == "--tests"));
+    }
+
+    #[test]
+    fn strip_ansi_sequences() {
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: x"), "error: x");
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x07link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        assert_eq!(strip_ansi("\x1b7saved\x1b8"), "saved");
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("cli", "cli"), 0);
+        assert_eq!(edit_distance("cli", "clli"), 1);
+        assert_eq!(edit_distance("cli", "cil"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    fn package(targets: &str) -> Package {
+        let json = format!(
+            r#"{{
+                "name": "cli",
+                "version": "0.1.0",
+                "id": "cli 0.1.0 (path+file:///cli)",
+                "dependencies": [],
+                "features": {{"default": ["color"], "color": [], "extra": []}},
+                "manifest_path": "/cli/Cargo.toml",
+                "targets": [{}]
+            }}"#,
+            targets
+        );
+        serde_json::from_str(&json).expect("valid package")
+    }
+
+    fn bin_exe_vars(names: &[&str]) -> Vec<(String, String)> {
+        let exe = std::env::current_exe().unwrap().display().to_string();
+        names
+            .iter()
+            .map(|name| (format!("CARGO_BIN_EXE_{}", name), exe.clone()))
+            .collect()
+    }
+
+    const CLI: &str = r#"{"name": "cli", "kind": ["bin"], "src_path": "/cli/src/main.rs"}"#;
+
+    #[test]
+    fn bin_exe_env_applies_to_plain_builders() {
+        assert!(!BinTest::with().affects_build());
+        let builder = BinTest::with()
+            .build_executable("cli")
+            .all_bins(true)
+            .capture_stderr(true)
+            .timeout(Duration::from_secs(60));
+        assert!(!builder.affects_build());
+
+        let executables = builder
+            .bin_exe_executables(&package(CLI), bin_exe_vars(&["cli"]))
+            .expect("taken from the variables");
+        assert_eq!(executables.keys().collect::<Vec<_>>(), ["cli"]);
+    }
+
+    #[test]
+    fn bin_exe_env_bypassed() {
+        assert!(BinTest::with().build_workspace(true).affects_build());
+        assert!(BinTest::with().features(["extra"]).affects_build());
+        assert!(BinTest::with().no_default_features(true).affects_build());
+        assert!(BinTest::with().release(true).affects_build());
+        assert!(BinTest::with()
+            .use_bin_exe_env(false)
+            .bin_exe_env(&OnceCell::new())
+            .is_none());
+    }
+
+    #[test]
+    fn bin_exe_env_falls_back_to_cargo() {
+        // a requested executable without a variable
+        let builder = BinTest::with().build_executable("server");
+        assert!(builder
+            .bin_exe_executables(&package(CLI), bin_exe_vars(&["cli"]))
+            .is_none());
+
+        // a variable of a file that does not exist
+        let vars = vec![(
+            String::from("CARGO_BIN_EXE_cli"),
+            String::from("/nonexistent/cli"),
+        )];
+        assert!(BinTest::with()
+            .bin_exe_executables(&package(CLI), vars)
+            .is_none());
+
+        // a binary which needs a feature beyond the default ones
+        let gated = format!(
+            r#"{}, {{"name": "tool", "kind": ["bin"], "src_path": "/cli/src/bin/tool.rs",
+                "required-features": ["extra"]}}"#,
+            CLI
+        );
+        assert!(BinTest::with()
+            .bin_exe_executables(&package(&gated), bin_exe_vars(&["cli", "tool"]))
+            .is_none());
+
+        // default features are enabled for the test
+        let default = r#"{"name": "cli", "kind": ["bin"], "src_path": "/cli/src/main.rs",
+            "required-features": ["color"]}"#;
+        assert!(BinTest::with()
+            .bin_exe_executables(&package(default), bin_exe_vars(&["cli"]))
+            .is_some());
+    }
+
+    #[test]
+    fn build_variables() {
+        for key in [
+            "CARGO",
+            "CARGO_MANIFEST_DIR",
+            "CARGO_PKG_NAME",
+            "CARGO_BIN_EXE_cli",
+            "OUT_DIR",
+            "RUSTUP_TOOLCHAIN",
+        ] {
+            assert!(is_build_variable(key), "{}", key);
+        }
+        for key in [
+            "CARGO_HOME",
+            "CARGO_TARGET_DIR",
+            "CARGO_NET_OFFLINE",
+            "RUSTUP_HOME",
+            "DEBUG",
+            "TARGET",
+            "PATH",
+        ] {
+            assert!(!is_build_variable(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn glob_match_wildcards() {
+        assert!(glob_match("cli", "cli", false));
+        assert!(glob_match("cli-*", "cli-server", false));
+        assert!(glob_match("*", "", false));
+        assert!(glob_match("c?i", "cli", false));
+        assert!(glob_match("*-*-test", "a-b-c-test", false));
+        assert!(!glob_match("cli-*", "cli", false));
+        assert!(!glob_match("c?i", "ci", false));
+        assert!(!glob_match("CLI", "cli", false));
+        assert!(glob_match("CLI-*", "cli-server", true));
+    }
+
+    #[test]
+    fn shell_quoting() {
+        assert_eq!(shell_quote(OsStr::new("--bin=cli")), "--bin=cli");
+        assert_eq!(shell_quote(OsStr::new("")), "''");
+        assert_eq!(shell_quote(OsStr::new("two words")), "'two words'");
+        assert_eq!(shell_quote(OsStr::new("it's")), "'it'\\''s'");
+    }
+
+    #[test]
+    fn bin_env_names() {
+        assert_eq!(bin_env_name("cli"), "BINTEST_BIN_CLI");
+        assert_eq!(bin_env_name("my-tool"), "BINTEST_BIN_MY_TOOL");
+        assert_eq!(bin_env_name("tool.v2"), "BINTEST_BIN_TOOL_V2");
+        assert_eq!(bin_env_name("Mixed_Case9"), "BINTEST_BIN_MIXED_CASE9");
+        assert_eq!(bin_env_name("example/demo"), "BINTEST_BIN_EXAMPLE_DEMO");
+        assert_eq!(bin_env_name("pkg::tool"), "BINTEST_BIN_PKG__TOOL");
+    }
+
+    #[test]
+    fn container_paths() {
+        let mounts = [
+            (
+                Utf8PathBuf::from("/target"),
+                Utf8PathBuf::from("/host/target"),
+            ),
+            (
+                Utf8PathBuf::from("/project"),
+                Utf8PathBuf::from("/host/project"),
+            ),
+        ];
+        let mut path = Utf8PathBuf::from("/target/aarch64-unknown-linux-gnu/debug/cli");
+        map_container_path(&mut path, &mounts);
+        assert_eq!(path, "/host/target/aarch64-unknown-linux-gnu/debug/cli");
+
+        let mut path = Utf8PathBuf::from("/elsewhere/cli");
+        map_container_path(&mut path, &mounts);
+        assert_eq!(path, "/elsewhere/cli");
+    }
+
+    #[test]
+    fn normalized_paths() {
+        let path = Utf8Path::new("/work/target/x86_64-unknown-linux-gnu/debug/cli");
+        assert_eq!(normalized_path(path, "debug", false), "cli");
+        assert_eq!(normalized_path(path, "debug", true), "debug/cli");
+        let path = Utf8Path::new("/work/target/release/examples/demo.exe");
+        assert_eq!(normalized_path(path, "release", false), "examples/demo");
+        let path = Utf8Path::new("/work/target/debug/deps/tool-0123456789abcdef");
+        assert_eq!(normalized_path(path, "debug", false), "deps/tool");
+        // a name ending in something else than the hash is kept
+        let path = Utf8Path::new("/work/target/debug/deps/tool-helper");
+        assert_eq!(normalized_path(path, "debug", false), "deps/tool-helper");
+        // outside of the profile directory only the file name is kept
+        let path = Utf8Path::new("/opt/bin/cli");
+        assert_eq!(normalized_path(path, "debug", true), "cli");
+    }
+
+    #[test]
+    fn merge_libraries() {
+        let build = |libraries: &[(&str, &str)]| {
+            let registry = cache::Registry {
+                libraries: libraries
+                    .iter()
+                    .map(|(name, path)| (name.to_string(), Utf8PathBuf::from(path)))
+                    .collect(),
+                ..Default::default()
+            };
+            BinTest::from_registry(registry, &BinTestBuilder::default(), BuildSource::Paths)
+        };
+        let first = || build(&[("core", "/a/libcore.rlib"), ("util", "/a/libutil.rlib")]);
+        let second = || build(&[("util", "/b/libutil.rlib"), ("extra", "/b/libextra.rlib")]);
+
+        let err = first().merge(second()).unwrap_err();
+        assert!(err.names.is_empty());
+        assert_eq!(err.libraries, ["util"]);
+        let err = first()
+            .merge_with(second(), MergePolicy::Qualify)
+            .unwrap_err();
+        assert_eq!(err.libraries, ["util"]);
+
+        let merged = first().merge_with(second(), MergePolicy::KeepLast).unwrap();
+        let libraries: Vec<_> = merged.list_libraries().collect();
+        assert_eq!(libraries.len(), 3);
+        assert_eq!(merged.library_path("util").unwrap(), "/b/libutil.rlib");
+        assert_eq!(merged.library_path("extra").unwrap(), "/b/libextra.rlib");
+        let merged = first()
+            .merge_with(second(), MergePolicy::KeepFirst)
+            .unwrap();
+        assert_eq!(merged.library_path("util").unwrap(), "/a/libutil.rlib");
+    }
+
+    #[test]
+    fn merge_build_scripts() {
+        let build = |out_dir: &str| {
+            let mut bintest = BinTest::from_registry(
+                cache::Registry::default(),
+                &BinTestBuilder::default(),
+                BuildSource::Paths,
+            );
+            let json = format!(
+                r#"{{"package_id": "gen 0.1.0", "linked_libs": [], "linked_paths": [],
+                    "cfgs": [], "env": [], "out_dir": "{}"}}"#,
+                out_dir
+            );
+            let script: BuildScript = serde_json::from_str(&json).expect("valid build script");
+            bintest.build_scripts.insert(String::from("gen"), script);
+            bintest
+        };
+
+        let merged = build("/a/out").merge(build("/a/out")).unwrap();
+        assert_eq!(merged.out_dir("gen").unwrap(), "/a/out");
+
+        let err = build("/a/out").merge(build("/b/out")).unwrap_err();
+        assert_eq!(err.build_scripts, ["gen"]);
+        let err = build("/a/out")
+            .merge_with(build("/b/out"), MergePolicy::Qualify)
+            .unwrap_err();
+        assert_eq!(err.build_scripts, ["gen"]);
+
+        let merged = build("/a/out")
+            .merge_with(build("/b/out"), MergePolicy::KeepFirst)
+            .unwrap();
+        assert_eq!(merged.out_dir("gen").unwrap(), "/a/out");
+        let merged = build("/a/out")
+            .merge_with(build("/b/out"), MergePolicy::KeepLast)
+            .unwrap();
+        assert_eq!(merged.out_dir("gen").unwrap(), "/b/out");
+    }
+
+    #[test]
+    fn merge_debug_infos() {
+        let build = |package: &str, dir: &str| {
+            let registry = cache::Registry {
+                executables: BTreeMap::from([(
+                    String::from("cli"),
+                    Utf8PathBuf::from(dir).join("cli.exe"),
+                )]),
+                debug_infos: BTreeMap::from([(
+                    String::from("cli"),
+                    Utf8PathBuf::from(dir).join("cli.pdb"),
+                )]),
+                infos: BTreeMap::from([(
+                    String::from("cli"),
+                    ExecutableInfo {
+                        package_id: format!("{} 0.1.0", package),
+                        package: String::from(package),
+                        kind: String::from("bin"),
+                        features: Vec::new(),
+                        profile: String::from("dev"),
+                        #[cfg(feature = "artifact")]
+                        artifact: None,
+                    },
+                )]),
+                ..Default::default()
+            };
+            BinTest::from_registry(registry, &BinTestBuilder::default(), BuildSource::Paths)
+        };
+
+        let merged = build("app", "/a").merge(build("fixture", "/a")).unwrap();
+        assert_eq!(merged.debug_info("cli").unwrap(), "/a/cli.pdb");
+
+        let merged = build("app", "/a")
+            .merge_with(build("fixture", "/b"), MergePolicy::KeepLast)
+            .unwrap();
+        assert_eq!(merged.debug_info("cli").unwrap(), "/b/cli.pdb");
+
+        let merged = build("app", "/a")
+            .merge_with(build("fixture", "/b"), MergePolicy::Qualify)
+            .unwrap();
+        assert_eq!(merged.debug_info("app/cli").unwrap(), "/a/cli.pdb");
+        assert_eq!(merged.debug_info("fixture/cli").unwrap(), "/b/cli.pdb");
+
+        let merged = BinTest::from_registry(
+            cache::Registry::default(),
+            &BinTestBuilder::default(),
+            BuildSource::Paths,
+        )
+        .merge(build("fixture", "/b"))
+        .unwrap();
+        assert_eq!(merged.debug_info("cli").unwrap(), "/b/cli.pdb");
     }
 }
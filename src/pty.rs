@@ -0,0 +1,313 @@
+use std::ffi::{CStr, OsStr};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::raw::{c_char, c_int, c_ulong, c_ushort};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+extern "C" {
+    fn posix_openpt(flags: c_int) -> c_int;
+    fn grantpt(fd: c_int) -> c_int;
+    fn unlockpt(fd: c_int) -> c_int;
+    fn ptsname(fd: c_int) -> *mut c_char;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    fn setsid() -> c_int;
+}
+
+const O_RDWR: c_int = 2;
+#[cfg(target_os = "linux")]
+const O_NOCTTY: c_int = 0o400;
+#[cfg(target_os = "macos")]
+const O_NOCTTY: c_int = 0x20000;
+#[cfg(target_os = "linux")]
+const TIOCSWINSZ: c_ulong = 0x5414;
+#[cfg(target_os = "macos")]
+const TIOCSWINSZ: c_ulong = 0x8008_7467;
+#[cfg(target_os = "linux")]
+const TIOCSCTTY: c_ulong = 0x540e;
+#[cfg(target_os = "macos")]
+const TIOCSCTTY: c_ulong = 0x2000_7461;
+
+#[repr(C)]
+struct WinSize {
+    rows: c_ushort,
+    cols: c_ushort,
+    x_pixels: c_ushort,
+    y_pixels: c_ushort,
+}
+
+/// A command that runs attached to a pseudo terminal, created by `BinTest::command_pty()`.
+/// Only supported on Linux and macOS, spawning fails with `ErrorKind::Unsupported` elsewhere.
+#[derive(Debug)]
+pub struct PtyCommand {
+    command: Command,
+    rows: u16,
+    cols: u16,
+}
+
+impl PtyCommand {
+    pub(crate) fn new(command: Command) -> PtyCommand {
+        PtyCommand {
+            command,
+            rows: 24,
+            cols: 80,
+        }
+    }
+
+    /// Adds an argument
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
+        self.command.arg(arg);
+        self
+    }
+
+    /// Adds arguments
+    pub fn args<I: IntoIterator<Item = S>, S: AsRef<OsStr>>(&mut self, args: I) -> &mut Self {
+        self.command.args(args);
+        self
+    }
+
+    /// Sets an environment variable, 'TERM' is not set by default
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) -> &mut Self {
+        self.command.env(key, value);
+        self
+    }
+
+    /// Sets the size of the terminal, 24 rows and 80 columns by default
+    pub fn size(&mut self, rows: u16, cols: u16) -> &mut Self {
+        self.rows = rows;
+        self.cols = cols;
+        self
+    }
+
+    /// Gives access to the underlying 'std::process::Command', stdio is replaced by the
+    /// terminal when spawning
+    pub fn command_mut(&mut self) -> &mut Command {
+        &mut self.command
+    }
+
+    /// Allocates a pseudo terminal and spawns the executable as session leader with the
+    /// terminal as its controlling terminal and stdio
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn spawn(&mut self) -> io::Result<PtyChild> {
+        let (master, slave) = open_pty(self.rows, self.cols)?;
+        let child = unsafe {
+            self.command
+                .stdin(Stdio::from(slave.try_clone()?))
+                .stdout(Stdio::from(slave.try_clone()?))
+                .stderr(Stdio::from(slave))
+                // a new session without a controlling terminal, stdin becomes it then
+                .pre_exec(|| {
+                    if setsid() < 0 || ioctl(0, TIOCSCTTY, 0 as c_int) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                })
+                .spawn()?
+        };
+        // the copies of the slave in the command would keep the terminal open
+        self.command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        let captured = Arc::new(Captured::default());
+        let mut reader = master.try_clone()?;
+        let thread_captured = Arc::clone(&captured);
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            // reading fails with 'EIO' on Linux once the terminal is closed
+            while let Ok(read @ 1..) = reader.read(&mut buffer) {
+                thread_captured.push(&buffer[..read]);
+            }
+            thread_captured.close();
+        });
+
+        Ok(PtyChild {
+            child,
+            master,
+            captured,
+        })
+    }
+
+    /// Pseudo terminals are not supported on this platform
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn spawn(&mut self) -> io::Result<PtyChild> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pseudo terminals are not supported on this platform",
+        ))
+    }
+}
+
+/// Opens a pseudo terminal with the given size, returns the master and the slave side
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn open_pty(rows: u16, cols: u16) -> io::Result<(File, File)> {
+    let check = |result: c_int| {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    };
+    unsafe {
+        let master = File::from_raw_fd(check(posix_openpt(O_RDWR | O_NOCTTY))?);
+        check(grantpt(master.as_raw_fd()))?;
+        check(unlockpt(master.as_raw_fd()))?;
+        let size = WinSize {
+            rows,
+            cols,
+            x_pixels: 0,
+            y_pixels: 0,
+        };
+        check(ioctl(
+            master.as_raw_fd(),
+            TIOCSWINSZ,
+            &size as *const WinSize,
+        ))?;
+        // copied before anything else could call 'ptsname()' again
+        let name = ptsname(master.as_raw_fd());
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+        let slave = File::options().read(true).write(true).open(name)?;
+        Ok((master, slave))
+    }
+}
+
+/// Terminal output collected by the reader thread
+#[derive(Default)]
+struct Captured {
+    state: Mutex<(Vec<u8>, bool)>,
+    changed: Condvar,
+}
+
+impl Captured {
+    fn push(&self, data: &[u8]) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.0.extend_from_slice(data);
+        self.changed.notify_all();
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.1 = true;
+        self.changed.notify_all();
+    }
+}
+
+impl std::fmt::Debug for Captured {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Captured").finish_non_exhaustive()
+    }
+}
+
+/// An executable running attached to a pseudo terminal. Writing sends input like typing on
+/// the terminal, everything the executable prints is captured.
+#[derive(Debug)]
+pub struct PtyChild {
+    child: Child,
+    master: File,
+    captured: Arc<Captured>,
+}
+
+/// The result of `PtyChild::wait()`
+#[derive(Debug, Clone)]
+pub struct PtyOutput {
+    /// Exit status of the executable
+    pub status: ExitStatus,
+    /// Everything written to the terminal, with the '\r\n' line endings of the terminal
+    pub output: Vec<u8>,
+}
+
+impl PtyChild {
+    /// Returns the process id
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Sends Ctrl-<key>, 'c' interrupts the executable and 'd' ends the input
+    pub fn send_control(&mut self, key: char) -> io::Result<()> {
+        let key = key.to_ascii_uppercase();
+        if !('@'..='_').contains(&key) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no control character for <<{}>>", key),
+            ));
+        }
+        self.master.write_all(&[key as u8 - b'@'])
+    }
+
+    /// Returns everything written to the terminal so far
+    pub fn output(&self) -> Vec<u8> {
+        let state = self
+            .captured
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        state.0.clone()
+    }
+
+    /// Waits until the terminal output contains `pattern`, for answering prompts. Returns
+    /// false when the timeout expired or the terminal was closed before.
+    pub fn wait_for(&self, pattern: &str, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self
+            .captured
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if String::from_utf8_lossy(&state.0).contains(pattern) {
+                return true;
+            }
+            let now = Instant::now();
+            if state.1 || now >= deadline {
+                return false;
+            }
+            state = self
+                .captured
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    /// Waits for the executable to exit and returns everything it wrote to the terminal
+    pub fn wait(mut self) -> io::Result<PtyOutput> {
+        let status = self.child.wait()?;
+        let mut state = self
+            .captured
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // children of the executable may keep the terminal open
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while !state.1 && Instant::now() < deadline {
+            state = self
+                .captured
+                .changed
+                .wait_timeout(state, deadline - Instant::now())
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        Ok(PtyOutput {
+            status,
+            output: std::mem::take(&mut state.0),
+        })
+    }
+}
+
+impl Write for PtyChild {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.master.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.master.flush()
+    }
+}
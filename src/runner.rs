@@ -0,0 +1,247 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A program the executables are started with, like the 'runner' of the cargo configuration.
+/// Set by `BinTestBuilder::runner()` or discovered for the target, see `BinTest::runner()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Runner {
+    /// The program to run
+    pub program: OsString,
+    /// Arguments given before the path of the executable
+    pub args: Vec<OsString>,
+    /// Environment variables set for the runner
+    pub env: Vec<(OsString, OsString)>,
+}
+
+impl Runner {
+    /// Creates a runner starting `program` with `args` and then the executable
+    pub fn new<P, I, A>(program: P, args: I) -> Runner
+    where
+        P: Into<OsString>,
+        I: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        Runner {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            env: Vec::new(),
+        }
+    }
+
+    /// Runs Windows executables under 'wine' with 'WINEDEBUG=-all', which keeps the output
+    /// free of wine diagnostics. Wine maps the current directory itself, paths given as
+    /// arguments can be translated with `Runner::wine_path()`.
+    pub fn wine() -> Runner {
+        Runner::new("wine", Vec::<OsString>::new()).env("WINEDEBUG", "-all")
+    }
+
+    /// Runs the executables under valgrind's memcheck, memory errors and leaks make them
+    /// exit with 101
+    pub fn valgrind() -> Runner {
+        Runner::new("valgrind", ["--error-exitcode=101", "--leak-check=full"])
+    }
+
+    /// Sets an environment variable for the runner
+    pub fn env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Translates a unix path to the path wine sees, absolute paths are on drive 'Z:'
+    pub fn wine_path<P: AsRef<Path>>(path: P) -> String {
+        let path = path.as_ref().to_string_lossy().replace('/', "\\");
+        if path.starts_with('\\') {
+            format!("Z:{}", path)
+        } else {
+            path
+        }
+    }
+
+    /// Splits a runner given as one string at whitespace, the way cargo does
+    fn from_words(words: &str) -> Option<Runner> {
+        let mut words = words.split_whitespace();
+        Some(Runner::new(words.next()?, words))
+    }
+
+    /// Constructs a command running the executable under the runner
+    pub(crate) fn command(&self, executable: &Path) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .arg(executable)
+            .envs(self.env.iter().map(|(key, value)| (key, value)));
+        command
+    }
+
+    /// Returns true when the program exists, programs without a directory are searched in
+    /// 'PATH'
+    pub fn is_installed(&self) -> bool {
+        let program = Path::new(&self.program);
+        if program.components().count() > 1 {
+            return program.is_file();
+        }
+        let suffixes: &[&str] = if cfg!(windows) { &["", ".exe"] } else { &[""] };
+        std::env::var_os("PATH").is_some_and(|path| {
+            std::env::split_paths(&path).any(|dir| {
+                suffixes.iter().any(|suffix| {
+                    let mut name = OsString::from(program.as_os_str());
+                    name.push(OsStr::new(suffix));
+                    dir.join(name).is_file()
+                })
+            })
+        })
+    }
+}
+
+/// Finds the runner cargo would use for `target`: 'CARGO_TARGET_<TRIPLE>_RUNNER' first, then
+/// 'target.<triple>.runner' from the '.cargo/config.toml' files from the current directory
+/// upwards and from 'CARGO_HOME'. 'target.<cfg>.runner' entries are not evaluated.
+pub(crate) fn discover(target: &str) -> Option<Runner> {
+    let key = format!(
+        "CARGO_TARGET_{}_RUNNER",
+        target.to_uppercase().replace(['-', '.'], "_")
+    );
+    if let Some(value) = std::env::var_os(key) {
+        return Runner::from_words(&value.to_string_lossy());
+    }
+    config_files()
+        .into_iter()
+        .find_map(|file| runner_in_config(&fs::read_to_string(file).ok()?, target))
+}
+
+/// The cargo configuration files in the order cargo gives them precedence
+fn config_files() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::current_dir()
+        .map(|cwd| cwd.ancestors().map(|dir| dir.join(".cargo")).collect())
+        .unwrap_or_default();
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")));
+    dirs.extend(cargo_home);
+    dirs.iter()
+        .flat_map(|dir| [dir.join("config.toml"), dir.join("config")])
+        .filter(|file| file.is_file())
+        .collect()
+}
+
+/// Looks for the runner of `target` in a cargo configuration file, either in a
+/// '[target.<triple>]' table or as a dotted 'target.<triple>.runner' key. Only the TOML
+/// cargo configurations use in practice is understood, arrays must be on one line.
+fn runner_in_config(contents: &str, target: &str) -> Option<Runner> {
+    let table = format!("target.{}", target);
+    let dotted = format!("target.{}.runner", target);
+    let mut current = String::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            current = normalize_key(header.split(']').next().unwrap_or_default());
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = normalize_key(key);
+        if (current == table && key == "runner") || (current.is_empty() && key == dotted) {
+            return parse_runner(value.trim());
+        }
+    }
+    None
+}
+
+/// Removes quotes and whitespace from a TOML key
+fn normalize_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| !c.is_whitespace() && *c != '"' && *c != '\'')
+        .collect()
+}
+
+/// Parses a runner value, a string split at whitespace or an array of strings
+fn parse_runner(value: &str) -> Option<Runner> {
+    match value.strip_prefix('[') {
+        Some(array) => {
+            let mut words = Vec::new();
+            let mut rest = array.trim_start();
+            while let Some((word, tail)) = parse_string(rest) {
+                words.push(word);
+                rest = tail.trim_start().trim_start_matches(',').trim_start();
+            }
+            let mut words = words.into_iter();
+            Some(Runner::new(words.next()?, words))
+        }
+        None => Runner::from_words(&parse_string(value)?.0),
+    }
+}
+
+/// Parses a quoted TOML string at the start of `value`, returns it and the rest
+fn parse_string(value: &str) -> Option<(String, &str)> {
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let mut string = String::new();
+    let mut chars = value[1..].char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((string, &value[index + 2..])),
+            '\\' if quote == '"' => string.extend(chars.next().map(|(_, c)| c)),
+            c => string.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_runner, runner_in_config, Runner};
+
+    const TARGET: &str = "aarch64-unknown-linux-gnu";
+
+    #[test]
+    fn runner_in_table() {
+        let config = "[build]\n\
+                      target = \"x86_64-unknown-linux-gnu\"\n\
+                      \n\
+                      [target.aarch64-unknown-linux-gnu]\n\
+                      linker = \"aarch64-linux-gnu-gcc\"\n\
+                      runner = \"qemu-aarch64 -L /usr/aarch64-linux-gnu\"\n";
+        assert_eq!(
+            runner_in_config(config, TARGET),
+            Some(Runner::new(
+                "qemu-aarch64",
+                ["-L", "/usr/aarch64-linux-gnu"]
+            ))
+        );
+        assert_eq!(runner_in_config(config, "x86_64-unknown-linux-gnu"), None);
+    }
+
+    #[test]
+    fn runner_as_dotted_key() {
+        let config = "target.aarch64-unknown-linux-gnu.runner = ['qemu-aarch64', '-L', '/usr']";
+        assert_eq!(
+            runner_in_config(config, TARGET),
+            Some(Runner::new("qemu-aarch64", ["-L", "/usr"]))
+        );
+    }
+
+    #[test]
+    fn runner_in_quoted_table() {
+        let config = "[target.\"aarch64-unknown-linux-gnu\"]\nrunner = \"qemu-aarch64\"";
+        assert_eq!(
+            runner_in_config(config, TARGET),
+            Some(Runner::new("qemu-aarch64", Vec::<String>::new()))
+        );
+    }
+
+    #[test]
+    fn runner_values() {
+        assert_eq!(
+            parse_runner(r#"["wasmtime", "run", "--dir", "."]"#),
+            Some(Runner::new("wasmtime", ["run", "--dir", "."]))
+        );
+        assert_eq!(
+            parse_runner(r#""C:\\tools\\runner.exe""#),
+            Some(Runner::new(r"C:\tools\runner.exe", Vec::<String>::new()))
+        );
+        assert_eq!(parse_runner("[]"), None);
+        assert_eq!(parse_runner("\"unterminated"), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cargo_metadata::camino::Utf8PathBuf;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{cache, BinTest, BinTestBuilder, BuildSource, Error, ExecutableInfo};
+
+// bump when the layout of `write_manifest()` changes incompatibly
+const MANIFEST_VERSION: u64 = 1;
+
+/// The registered executables of a `BinTest`, for handing them to other tools. With the
+/// 'serde' feature it can be serialized, paths are plain strings then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistrySnapshot {
+    /// Name of the profile the executables were build with
+    pub profile: String,
+    /// The target triple when cross compiling
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub target: Option<String>,
+    /// The registered executables, sorted by name
+    pub executables: Vec<SnapshotExecutable>,
+    /// Bare names shared by executables of several packages, with their qualified names
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub ambiguous: BTreeMap<String, Vec<String>>,
+}
+
+/// One executable of a `RegistrySnapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotExecutable {
+    /// The name the executable is registered with
+    pub name: String,
+    /// Path of the executable, converted lossily when it is not valid UTF-8
+    pub path: Utf8PathBuf,
+    /// Kind of the target, one of 'bin', 'example', 'test' or 'bench'
+    pub kind: String,
+    /// Name of the package the executable belongs to, when known
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub package: Option<String>,
+    /// Id of the package the executable belongs to, when known
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub package_id: Option<String>,
+    /// The features the package was build with
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub features: Vec<String>,
+}
+
+impl BinTest {
+    /// Returns the registered executables as a `RegistrySnapshot`
+    pub fn snapshot(&self) -> RegistrySnapshot {
+        RegistrySnapshot {
+            profile: self.profile.clone(),
+            target: self.target.clone(),
+            executables: self
+                .build_executables
+                .iter()
+                .map(|(name, path)| {
+                    let info = self.infos.get(name);
+                    SnapshotExecutable {
+                        name: name.clone(),
+                        path: path.clone(),
+                        kind: info.map_or_else(|| String::from("bin"), |info| info.kind.clone()),
+                        package: info.map(|info| info.package.clone()),
+                        package_id: info.map(|info| info.package_id.clone()),
+                        features: info.map(|info| info.features.clone()).unwrap_or_default(),
+                    }
+                })
+                .collect(),
+            ambiguous: self.ambiguous.clone(),
+        }
+    }
+
+    /// Restores a `BinTest` from a snapshot without running cargo. Fails with
+    /// `Error::InvalidExecutables` when an executable does not exist anymore.
+    /// `rebuild()` builds with the default configuration.
+    pub fn from_snapshot(snapshot: RegistrySnapshot) -> Result<BinTest, Error> {
+        let bintest = BinTest::from_snapshot_unverified(snapshot);
+        let report = bintest.verify();
+        if report.is_ok() {
+            Ok(bintest)
+        } else {
+            Err(Error::InvalidExecutables(report))
+        }
+    }
+
+    /// Restores a `BinTest` from a snapshot without checking the executables
+    pub fn from_snapshot_unverified(snapshot: RegistrySnapshot) -> BinTest {
+        let mut registry = cache::Registry {
+            profile: snapshot.profile,
+            target: snapshot.target,
+            ambiguous: snapshot.ambiguous,
+            ..Default::default()
+        };
+        for executable in snapshot.executables {
+            if let (Some(package), Some(package_id)) = (executable.package, executable.package_id) {
+                registry.infos.insert(
+                    executable.name.clone(),
+                    ExecutableInfo {
+                        package_id,
+                        package,
+                        kind: executable.kind,
+                        features: executable.features,
+                        profile: registry.profile.clone(),
+                        #[cfg(feature = "artifact")]
+                        artifact: None,
+                    },
+                );
+            }
+            registry
+                .executables
+                .insert(executable.name, executable.path);
+        }
+        BinTest::from_registry(registry, &BinTestBuilder::default(), BuildSource::Snapshot)
+    }
+
+    /// Writes the registered executables as JSON for test harnesses in other languages:
+    /// `{"schema_version": 1, "profile": "dev", "target": null, "executables": [{"name":
+    /// "cli", "path": "/abs/target/debug/cli", "kind": "bin"}]}`. Paths are absolute. The
+    /// file is written aside and renamed, readers never see a partial file.
+    pub fn write_manifest<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let cwd = std::env::current_dir()?;
+        let executables: Vec<serde_json::Value> = self
+            .snapshot()
+            .executables
+            .into_iter()
+            .map(|executable| {
+                serde_json::json!({
+                    "name": executable.name,
+                    "path": cwd.join(executable.path).to_string_lossy(),
+                    "kind": executable.kind,
+                })
+            })
+            .collect();
+        let manifest = serde_json::json!({
+            "schema_version": MANIFEST_VERSION,
+            "profile": self.profile,
+            "target": self.target,
+            "executables": executables,
+        });
+
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "the path has no file name")
+        })?;
+        let partial = path.with_file_name(format!(
+            ".{}.{}.partial",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+        fs::write(&partial, serde_json::to_string_pretty(&manifest)? + "\n")?;
+        fs::rename(&partial, path).inspect_err(|_| {
+            let _ = fs::remove_file(&partial);
+        })
+    }
+
+    /// Reads a file written by `write_manifest()`, fails when an executable does not exist
+    /// anymore like `from_snapshot()`
+    pub fn read_manifest<P: AsRef<Path>>(path: P) -> Result<BinTest, Error> {
+        let invalid = |message: &str| {
+            Error::ReadManifest(io::Error::new(io::ErrorKind::InvalidData, message))
+        };
+        let contents = fs::read_to_string(path).map_err(Error::ReadManifest)?;
+        let manifest: serde_json::Value =
+            serde_json::from_str(&contents).map_err(|err| Error::ReadManifest(err.into()))?;
+        if manifest["schema_version"] != MANIFEST_VERSION {
+            return Err(invalid("unsupported schema_version"));
+        }
+
+        let string = |value: &serde_json::Value, key: &str| {
+            value[key]
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| invalid(&format!("'{}' must be a string", key)))
+        };
+        let mut executables = Vec::new();
+        for executable in manifest["executables"]
+            .as_array()
+            .ok_or_else(|| invalid("'executables' must be an array"))?
+        {
+            executables.push(SnapshotExecutable {
+                name: string(executable, "name")?,
+                path: Utf8PathBuf::from(string(executable, "path")?),
+                kind: string(executable, "kind")?,
+                package: None,
+                package_id: None,
+                features: Vec::new(),
+            });
+        }
+        BinTest::from_snapshot(RegistrySnapshot {
+            profile: string(&manifest, "profile")?,
+            target: manifest["target"].as_str().map(String::from),
+            executables,
+            ambiguous: BTreeMap::new(),
+        })
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Once;
+
+use bintest::{BinTest, BuildSource};
+
+// the variable cargo would set for a binary of this package
+fn set_bin_exe() -> String {
+    static SET: Once = Once::new();
+    let exe = std::env::current_exe().unwrap().display().to_string();
+    SET.call_once(|| std::env::set_var("CARGO_BIN_EXE_bintest-fake", &exe));
+    exe
+}
+
+#[test]
+fn taken_from_the_environment() {
+    let exe = set_bin_exe();
+    let bins = BinTest::with().build_executable("bintest-fake").build();
+    assert_eq!(bins.build_stats().source, BuildSource::BinExeEnv);
+    assert_eq!(bins.executable_path("bintest-fake").unwrap(), exe.as_str());
+}
+
+#[test]
+fn disabled_builds_with_cargo() {
+    set_bin_exe();
+    // cargo is run and does not know the executable
+    assert!(BinTest::with()
+        .build_executable("bintest-fake")
+        .use_bin_exe_env(false)
+        .try_build()
+        .map(|_| ())
+        .unwrap_err()
+        .to_string()
+        .contains("bintest-fake"));
+}
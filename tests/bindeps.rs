@@ -0,0 +1,26 @@
+use bintest::{BinTest, Error};
+
+#[test]
+fn dependency_executable_by_package() {
+    let bins = match BinTest::with()
+        .manifest_path("tests/fixtures/bindeps/Cargo.toml")
+        .toolchain("nightly")
+        .unstable_flag("bindeps")
+        .include_dependency_artifacts(true)
+        .try_build()
+    {
+        Ok(bins) => bins,
+        Err(Error::ToolchainNotInstalled(_)) => {
+            eprintln!("skipped, artifact dependencies need a nightly toolchain");
+            return;
+        }
+        Err(err) => panic!("{}", err),
+    };
+
+    let output = bins.command("dep::tool").output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "tool");
+
+    let output = bins.command_in_package("dep", "tool").output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "tool");
+}
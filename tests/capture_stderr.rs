@@ -0,0 +1,30 @@
+use bintest::{BinTest, Error};
+
+#[test]
+fn stderr_kept_on_success() {
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("pipes")
+        .capture_stderr(true)
+        .build();
+    assert!(
+        bins.build_output().contains("Finished"),
+        "{}",
+        bins.build_output()
+    );
+}
+
+#[test]
+fn stderr_in_the_error_on_failure() {
+    let result = BinTest::with()
+        .manifest_path("tests/fixtures/broken/Cargo.toml")
+        .capture_stderr(true)
+        .try_build();
+    match result {
+        Err(Error::BuildFailed(err)) => {
+            assert!(err.output.contains("could not compile"), "{}", err.output)
+        }
+        Err(err) => panic!("expected Error::BuildFailed, got {}", err),
+        Ok(_) => panic!("the fixture does not compile"),
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use bintest::{BinTest, Error, Runner};
+
+fn fixture() -> Arc<BinTest> {
+    BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .build_workspace(true)
+        .build_cached()
+}
+
+#[test]
+fn try_command_under_missing_runner() {
+    let bins = fixture();
+    let runner = Runner::new("bintest-no-such-runner", Vec::<String>::new());
+    match bins.try_command_under("producer", &runner) {
+        Err(Error::RunnerNotFound(program)) => assert_eq!(program, "bintest-no-such-runner"),
+        other => panic!("expected RunnerNotFound, got {:?}", other),
+    }
+}
+
+#[test]
+fn try_command_under_unknown_executable() {
+    let bins = fixture();
+    let runner = Runner::new(env!("CARGO"), Vec::<String>::new());
+    match bins.try_command_under("missing", &runner) {
+        Err(Error::Lookup(err)) => assert_eq!(err.name, "missing"),
+        other => panic!("expected Lookup, got {:?}", other),
+    }
+}
+
+#[test]
+fn contains_resolves_names_like_command() {
+    let bins = fixture();
+    assert!(bins.contains("producer"));
+    assert!(bins.contains("pipes::producer"));
+    assert!(!bins.contains("other::producer"));
+
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("pipes")
+        .lenient_lookup(true)
+        .build();
+    assert!(bins.contains("Producer.exe"));
+    assert!(bins.get("Producer.exe").is_some());
+}
+
+#[test]
+fn stdio_presets() {
+    let bins = fixture();
+    let output = bins
+        .command_piped("producer")
+        .arg("presets")
+        .spawn()
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        "presets"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).trim_end(),
+        "producer"
+    );
+
+    // the output of one piped command is the input of the next
+    let mut producer = bins.command_piped("producer").arg("piped").spawn().unwrap();
+    let output = bins
+        .command_piped("upper")
+        .stdin(producer.stdout.take().unwrap())
+        .spawn()
+        .unwrap()
+        .wait_with_output()
+        .unwrap();
+    assert!(producer.wait().unwrap().success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "PIPED");
+
+    let mut child = bins.command_null("producer").spawn().unwrap();
+    assert!(child.stdin.is_none() && child.stdout.is_none() && child.stderr.is_none());
+    assert!(child.wait().unwrap().success());
+
+    assert!(bins.command_inherit("producer").status().unwrap().success());
+}
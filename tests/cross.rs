@@ -0,0 +1,24 @@
+use bintest::{BinTest, Runner};
+
+#[test]
+fn builds_with_cross() {
+    if !Runner::new("cross", Vec::<String>::new()).is_installed() {
+        eprintln!("skipped, 'cross' is not installed");
+        return;
+    }
+
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("pipes")
+        .use_cross(true)
+        .target("aarch64-unknown-linux-gnu")
+        .target_dir(concat!(env!("CARGO_TARGET_TMPDIR"), "/cross"))
+        .build();
+
+    // the paths reported from inside the container are mapped back to the host
+    let path = bins.executable_path("producer").unwrap();
+    assert!(path.is_file(), "{}", path);
+    assert!(path
+        .components()
+        .any(|component| component.as_str() == "aarch64-unknown-linux-gnu"));
+}
@@ -0,0 +1,4 @@
+// does not compile
+fn main() {
+    let value: u32 = "not a number";
+}
@@ -0,0 +1,4 @@
+// stands in for a member that workspace builds exclude
+fn main() {
+    println!("fuzz");
+}
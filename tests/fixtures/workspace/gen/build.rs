@@ -0,0 +1,15 @@
+// writes a data file to 'OUT_DIR' and reports a cfg and variables, 'GEN_INJECTED' is passed
+// on from the environment of cargo
+use std::path::Path;
+
+fn main() {
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("data.txt"), "generated\n").unwrap();
+    println!("cargo:rustc-cfg=generated");
+    println!("cargo:rustc-env=GEN_VALUE=42");
+    if let Ok(injected) = std::env::var("GEN_INJECTED") {
+        println!("cargo:rustc-env=GEN_INJECTED={}", injected);
+    }
+    println!("cargo:rerun-if-env-changed=GEN_INJECTED");
+    println!("cargo:rerun-if-changed=build.rs");
+}
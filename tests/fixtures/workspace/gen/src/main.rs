@@ -0,0 +1,4 @@
+// prints the data file its build script generated
+fn main() {
+    print!("{}", include_str!(concat!(env!("OUT_DIR"), "/data.txt")));
+}
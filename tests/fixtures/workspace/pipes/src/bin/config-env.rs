@@ -0,0 +1,4 @@
+// prints 'BINTEST_CONFIG' as it was at compile time, set by '--config env.BINTEST_CONFIG=...'
+fn main() {
+    println!("{}", option_env!("BINTEST_CONFIG").unwrap_or("unset"));
+}
@@ -0,0 +1,9 @@
+use std::io::Read;
+
+// drains stdin and exits with status 3
+fn main() {
+    let mut input = String::new();
+    let _ = std::io::stdin().read_to_string(&mut input);
+    eprintln!("fail");
+    std::process::exit(3);
+}
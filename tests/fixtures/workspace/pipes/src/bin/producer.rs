@@ -0,0 +1,10 @@
+// prints its arguments as one line, 'hello' without any
+fn main() {
+    let words: Vec<String> = std::env::args().skip(1).collect();
+    if words.is_empty() {
+        println!("hello");
+    } else {
+        println!("{}", words.join(" "));
+    }
+    eprintln!("producer");
+}
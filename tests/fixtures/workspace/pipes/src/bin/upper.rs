@@ -0,0 +1,9 @@
+use std::io::Read;
+
+// copies stdin to stdout in upper case
+fn main() {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input).unwrap();
+    print!("{}", input.to_uppercase());
+    eprintln!("upper");
+}
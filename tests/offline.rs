@@ -0,0 +1,14 @@
+use bintest::{BinTest, Error};
+
+#[test]
+fn missing_dependency_offline() {
+    let result = BinTest::with()
+        .manifest_path("tests/fixtures/offline/Cargo.toml")
+        .offline(true)
+        .try_build();
+    match result {
+        Err(Error::Offline(status)) => assert!(!status.success()),
+        Err(err) => panic!("expected Error::Offline, got {}", err),
+        Ok(_) => panic!("the dependency can not be available offline"),
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use bintest::BinTest;
+
+fn fixture() -> Arc<BinTest> {
+    BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .build_workspace(true)
+        .build_cached()
+}
+
+#[test]
+fn pipeline_connects_stages() {
+    let bins = fixture();
+    let output = bins
+        .pipeline(&[("producer", &["piped", "words"]), ("upper", &[])])
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        "PIPED WORDS"
+    );
+}
+
+#[test]
+fn pipeline_single_stage() {
+    let bins = fixture();
+    let output = bins.pipeline(&[("producer", &[])]).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "hello");
+}
+
+#[test]
+fn pipeline_collects_stderr_in_order() {
+    let bins = fixture();
+    let output = bins
+        .pipeline(&[("producer", &[]), ("upper", &[]), ("upper", &[])])
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines, ["producer", "upper", "upper"]);
+}
+
+#[test]
+fn pipeline_reports_failed_stage() {
+    let bins = fixture();
+    let output = bins
+        .pipeline(&[("producer", &[]), ("fail", &[]), ("upper", &[])])
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "bintest: a pipeline needs at least one stage")]
+fn pipeline_without_stages() {
+    let _ = fixture().pipeline(&[]);
+}
+
+#[cfg(feature = "duct")]
+#[test]
+fn duct_pipe() {
+    let bins = fixture();
+    let shouted = bins
+        .duct_cmd("producer", ["duct"])
+        .pipe(bins.duct_cmd("upper", Vec::<&str>::new()))
+        .stderr_null()
+        .read()
+        .unwrap();
+    assert_eq!(shouted, "DUCT");
+}
@@ -0,0 +1,17 @@
+use bintest::BinTest;
+
+#[test]
+fn rebuild_reuses_the_ephemeral_target_dir() {
+    let mut bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("pipes")
+        .ephemeral_target_dir()
+        .build();
+    let path = bins.executable_path("producer").unwrap().to_owned();
+
+    let report = bins.rebuild().unwrap();
+    assert!(report.recompiled.is_empty(), "{:?}", report.recompiled);
+    assert!(report.moved.is_empty(), "{:?}", report.moved);
+    assert_eq!(bins.executable_path("producer").unwrap(), path);
+    assert!(path.is_file());
+}
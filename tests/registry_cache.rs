@@ -0,0 +1,17 @@
+use bintest::{BinTest, BuildSource};
+
+#[test]
+fn second_build_loaded_from_cache() {
+    let build = || {
+        BinTest::with()
+            .manifest_path("tests/fixtures/workspace/Cargo.toml")
+            .package("pipes")
+            .target_dir(concat!(env!("CARGO_TARGET_TMPDIR"), "/registry-cache"))
+            .registry_cache(true)
+            .build()
+    };
+    build();
+    let bins = build();
+    assert_eq!(bins.build_stats().source, BuildSource::DiskCache);
+    assert!(bins.contains("producer"));
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use bintest::BinTest;
+
+fn fixture() -> Arc<BinTest> {
+    BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .build_workspace(true)
+        .build_cached()
+}
+
+#[test]
+fn excluded_packages_are_not_registered() {
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .build_workspace(true)
+        .exclude("fuzz")
+        .build();
+    let names: Vec<&String> = bins.list_executables().map(|(name, _)| name).collect();
+    assert!(names.iter().any(|name| *name == "producer"), "{:?}", names);
+    assert!(!names.iter().any(|name| *name == "fuzz"), "{:?}", names);
+}
+
+#[test]
+fn cargo_config_flows_through() {
+    // a target directory of its own, the configuration changes what gets build
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("pipes")
+        .target_dir(concat!(env!("CARGO_TARGET_TMPDIR"), "/cargo-config"))
+        .cargo_arg("--config")
+        .cargo_arg("env.BINTEST_CONFIG=\"from-config\"")
+        .build();
+    let output = bins.command("config-env").output().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        "from-config"
+    );
+}
+
+#[test]
+fn all_bins_of_a_package() {
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("pipes")
+        .all_bins(true)
+        .build();
+    let names: Vec<&str> = bins.names().collect();
+    assert_eq!(names, ["config-env", "fail", "producer", "upper"]);
+}
+
+#[test]
+fn normalized_listing_is_machine_independent() {
+    // the same bytes on every platform, the CI runs this on linux and windows
+    assert_eq!(
+        fixture().normalized_listing(),
+        "config-env: config-env\n\
+         fail: fail\n\
+         fuzz: fuzz\n\
+         gen: gen\n\
+         producer: producer\n\
+         upper: upper\n"
+    );
+}
+
+#[test]
+fn normalized_entries_with_profile_dir() {
+    let entries = fixture().normalized_entries(true);
+    assert!(!entries.is_empty());
+    for entry in entries {
+        assert_eq!(entry.path, format!("debug/{}", entry.name));
+    }
+}
+
+#[test]
+fn build_script_out_dir() {
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("gen")
+        .collect_build_scripts(true)
+        .build();
+
+    let out_dir = bins.out_dir("gen").expect("gen has a build script");
+    assert_eq!(
+        std::fs::read_to_string(out_dir.join("data.txt")).unwrap(),
+        "generated\n"
+    );
+
+    let script = bins.build_script("gen").unwrap();
+    assert!(script.cfgs.iter().any(|cfg| cfg == "generated"));
+    assert!(script
+        .env
+        .iter()
+        .any(|(key, value)| key == "GEN_VALUE" && value == "42"));
+
+    let output = bins.command("gen").output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "generated\n");
+}
+
+#[test]
+fn env_reaches_build_scripts() {
+    // a target directory of its own, the variable makes the build script run again
+    let bins = BinTest::with()
+        .manifest_path("tests/fixtures/workspace/Cargo.toml")
+        .package("gen")
+        .target_dir(concat!(env!("CARGO_TARGET_TMPDIR"), "/injected-env"))
+        .env("GEN_INJECTED", "injected")
+        .collect_build_scripts(true)
+        .build();
+
+    let script = bins.build_script("gen").unwrap();
+    assert!(script
+        .env
+        .iter()
+        .any(|(key, value)| key == "GEN_INJECTED" && value == "injected"));
+}